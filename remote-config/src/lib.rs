@@ -0,0 +1,159 @@
+use core::Proxy;
+use error::RemoteError;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub confirm: Confirm,
+    pub proxy: ProxyConfig,
+    pub groups: BTreeMap<String, Group>,
+    pub vars: BTreeMap<String, String>,
+    pub aliases: BTreeMap<String, Alias>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    pub jump: Option<String>,
+    pub command: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Group {
+    pub targets: Vec<String>,
+    pub proxy: Option<ProxyConfig>,
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Alias {
+    pub command: String,
+    pub help: Option<String>,
+    pub args: Vec<String>,
+    pub options: BTreeMap<String, String>,
+    pub env: BTreeMap<String, String>,
+    pub targets: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Confirm {
+    pub threshold: usize,
+    pub patterns: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+pub enum Verdict {
+    Proceed,
+    Confirm(String),
+    Deny(String),
+}
+
+impl Default for Confirm {
+    fn default() -> Confirm {
+        Confirm {
+            threshold: 10,
+            patterns: vec![
+                r"\brm\b".to_string(),
+                r"\bshutdown\b".to_string(),
+                r"\breboot\b".to_string(),
+                r"\bpoweroff\b".to_string(),
+                r"\bmkfs".to_string(),
+            ],
+            deny: Vec::new(),
+        }
+    }
+}
+
+impl Confirm {
+    pub fn check(&self, hosts: usize, lines: &[String]) -> Result<Verdict, RemoteError> {
+        for pattern in &self.deny {
+            let regex = Regex::new(pattern)?;
+            if lines.iter().any(|line| regex.is_match(line)) {
+                return Ok(Verdict::Deny(pattern.clone()));
+            }
+        }
+        for pattern in &self.patterns {
+            let regex = Regex::new(pattern)?;
+            if lines.iter().any(|line| regex.is_match(line)) {
+                return Ok(Verdict::Confirm(format!("matches {}", pattern)));
+            }
+        }
+        if self.threshold > 0 && hosts > self.threshold {
+            return Ok(Verdict::Confirm(format!(
+                "more than {} hosts",
+                self.threshold
+            )));
+        }
+        Ok(Verdict::Proceed)
+    }
+}
+
+impl ProxyConfig {
+    pub fn proxy(&self) -> Result<Option<Proxy>, RemoteError> {
+        match (&self.jump, &self.command) {
+            (Some(_), Some(_)) => Err(RemoteError::new(
+                "proxy jump and proxy command are mutually exclusive",
+            )),
+            (Some(jump), None) => Ok(Some(Proxy::Jump(jump.clone()))),
+            (None, Some(command)) => Ok(Some(Proxy::Command(command.clone()))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl Config {
+    pub fn proxy(&self, host: &str) -> Result<Option<Proxy>, RemoteError> {
+        for group in self.groups.values() {
+            if let Some(proxy) = &group.proxy {
+                if group.targets.iter().any(|t| t.eq_ignore_ascii_case(host)) {
+                    return proxy.proxy();
+                }
+            }
+        }
+        self.proxy.proxy()
+    }
+
+    pub fn vars(&self, host: &str) -> BTreeMap<String, String> {
+        let mut vars = BTreeMap::new();
+        let groups = self
+            .groups
+            .values()
+            .filter(|group| group.targets.iter().any(|t| t.eq_ignore_ascii_case(host)));
+        for values in groups.map(|group| &group.vars).chain(Some(&self.vars)) {
+            for (name, value) in values {
+                vars.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        vars
+    }
+
+    pub fn path() -> PathBuf {
+        match env::var_os("REMOTE_CONFIG") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let mut path = PathBuf::from(env::var_os("HOME").unwrap_or_default());
+                path.push(".config/remote/config.toml");
+                path
+            }
+        }
+    }
+
+    pub fn load() -> Result<Config, RemoteError> {
+        let path = Config::path();
+        match read_to_string(&path) {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -28,7 +28,7 @@ pub fn load_chromium_cookies(db_path: &Path) -> Result<CookieJar, RemoteError> {
         let name: String = statement.read(3)?;
         let mut value: String = statement.read(4)?;
         let encrypted_value = &statement.read::<Vec<u8>>(5)?[3..];
-        if value == "" {
+        if value.is_empty() {
             let mut buffer = [0u8; 4096];
             let mut value_buffer = Vec::new();
             let mut read_buffer = RefReadBuffer::new(encrypted_value);
@@ -41,7 +41,7 @@ pub fn load_chromium_cookies(db_path: &Path) -> Result<CookieJar, RemoteError> {
                         .take_read_buffer()
                         .take_remaining()
                         .iter()
-                        .map(|&i| i),
+                        .copied(),
                 );
                 match result {
                     BufferResult::BufferUnderflow => break,
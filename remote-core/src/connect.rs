@@ -0,0 +1,185 @@
+use crate::proxy::{self, Proxy};
+use crate::{host, PASSWORD, USERNAME};
+use error::RemoteError;
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub handshake: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            connect: Duration::from_secs(5),
+            handshake: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ConnectOptions {
+    pub timeouts: Timeouts,
+    pub proxy: Option<Proxy>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub identity_file: Option<PathBuf>,
+    pub known_hosts: Option<PathBuf>,
+}
+
+impl ConnectOptions {
+    pub(crate) fn user(&self) -> &str {
+        self.user.as_ref().map_or(USERNAME, |u| u.as_str())
+    }
+
+    pub(crate) fn endpoint(&self, row: u8, column: u8) -> (String, u16) {
+        (
+            self.host.clone().unwrap_or_else(|| host(row, column)),
+            self.port.unwrap_or(22),
+        )
+    }
+}
+
+pub fn join_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+pub struct Probe {
+    pub address: String,
+    pub latency: Option<Duration>,
+    pub banner: Option<String>,
+    pub fingerprint: Option<String>,
+    pub authenticated: bool,
+    pub error: Option<RemoteError>,
+}
+
+pub(crate) fn open(
+    row: u8,
+    column: u8,
+    options: &ConnectOptions,
+) -> Result<TcpStream, RemoteError> {
+    let (host, port) = options.endpoint(row, column);
+    match &options.proxy {
+        Some(p) => proxy::open(p, &host, port, options.user(), &options.timeouts),
+        None => open_address(&join_host_port(&host, port), &options.timeouts),
+    }
+}
+
+pub(crate) fn open_address(address: &str, timeouts: &Timeouts) -> Result<TcpStream, RemoteError> {
+    let mut error = RemoteError::new("address not found");
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeouts.connect) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => error = e.into(),
+        }
+    }
+    Err(error)
+}
+
+pub(crate) fn handshake(tcp: TcpStream, timeouts: &Timeouts) -> Result<Session, RemoteError> {
+    let mut session = Session::new().unwrap();
+    session.set_tcp_stream(tcp);
+    session.set_timeout(timeouts.handshake.as_millis() as u32);
+    session.handshake()?;
+    Ok(session)
+}
+
+pub(crate) fn verify(
+    session: &Session,
+    row: u8,
+    column: u8,
+    options: &ConnectOptions,
+) -> Result<(), RemoteError> {
+    let path = match &options.known_hosts {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let (host, port) = options.endpoint(row, column);
+    let mut known_hosts = session.known_hosts()?;
+    known_hosts.read_file(path, KnownHostFileKind::OpenSSH)?;
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| RemoteError::new("no host key"))?;
+    match known_hosts.check_port(&host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(RemoteError::new(&format!(
+            "host key mismatch for {}",
+            join_host_port(&host, port)
+        ))),
+        CheckResult::NotFound => Err(RemoteError::new(&format!(
+            "host key for {} not found in {}",
+            join_host_port(&host, port),
+            path.display()
+        ))),
+        CheckResult::Failure => Err(RemoteError::new("cannot check host key")),
+    }
+}
+
+pub(crate) fn authenticate(session: &Session, options: &ConnectOptions) -> Result<(), RemoteError> {
+    let authenticated = match &options.identity_file {
+        Some(identity) => session
+            .userauth_pubkey_file(options.user(), None, identity, None)
+            .is_ok(),
+        None => false,
+    };
+    if !authenticated {
+        session.userauth_password(options.user(), PASSWORD)?;
+    }
+    session.set_timeout(0);
+    Ok(())
+}
+
+fn fingerprint(session: &Session) -> Option<String> {
+    session.host_key_hash(HashType::Sha1).map(|hash| {
+        let hex: Vec<String> = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("SHA1:{}", hex.join(":"))
+    })
+}
+
+pub fn probe(row: u8, column: u8, options: &ConnectOptions) -> Probe {
+    let (host, port) = options.endpoint(row, column);
+    let mut probe = Probe {
+        address: join_host_port(&host, port),
+        latency: None,
+        banner: None,
+        fingerprint: None,
+        authenticated: false,
+        error: None,
+    };
+    let start = Instant::now();
+    let tcp = match open(row, column, options) {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            probe.error = Some(e);
+            return probe;
+        }
+    };
+    probe.latency = Some(start.elapsed());
+    let session = match handshake(tcp, &options.timeouts) {
+        Ok(session) => session,
+        Err(e) => {
+            probe.error = Some(e);
+            return probe;
+        }
+    };
+    probe.banner = session.banner().map(|banner| banner.to_string());
+    probe.fingerprint = fingerprint(&session);
+    if let Err(e) = verify(&session, row, column, options) {
+        probe.error = Some(e);
+        return probe;
+    }
+    match authenticate(&session, options) {
+        Ok(()) => probe.authenticated = true,
+        Err(e) => probe.error = Some(e),
+    }
+    probe
+}
@@ -0,0 +1,360 @@
+use crate::connect::{self, join_host_port};
+use crate::escape::is_env_name;
+use crate::privilege::Escalation;
+use crate::{
+    recv_destination, send_destination, ConnectOptions, Output, Pump, RunOptions, Step,
+    LIBSSH2_ERROR_EAGAIN, PASSWORD,
+};
+use error::RemoteError;
+use ssh2::{BlockDirections, Channel, Session};
+use std::fs::{read, write};
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::runtime::Builder;
+use tokio::sync::Semaphore;
+use tokio::task::{self, LocalSet};
+use tokio::time::timeout;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+type Shard<P> = Vec<(usize, u8, u8, ConnectOptions, P)>;
+
+async fn wait(session: &Session, fd: &AsyncFd<TcpStream>) -> Result<(), RemoteError> {
+    let ready = async {
+        match session.block_directions() {
+            BlockDirections::Inbound => fd.readable().await.map(|mut g| g.clear_ready()),
+            BlockDirections::Outbound => fd.writable().await.map(|mut g| g.clear_ready()),
+            BlockDirections::Both => tokio::select! {
+                r = fd.readable() => r.map(|mut g| g.clear_ready()),
+                w = fd.writable() => w.map(|mut g| g.clear_ready()),
+            },
+            BlockDirections::None => {
+                task::yield_now().await;
+                Ok(())
+            }
+        }
+    };
+    match timeout(POLL_INTERVAL, ready).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Ok(()),
+    }
+}
+
+fn timed_out(what: &str) -> RemoteError {
+    RemoteError::new(&format!("{} timed out", what))
+}
+
+pub struct AsyncClient {
+    row: u8,
+    column: u8,
+    session: Session,
+    fd: AsyncFd<TcpStream>,
+}
+
+impl AsyncClient {
+    pub async fn connect(
+        row: u8,
+        column: u8,
+        options: &ConnectOptions,
+    ) -> Result<AsyncClient, RemoteError> {
+        let tcp = match &options.proxy {
+            Some(_) => {
+                let o = options.clone();
+                task::spawn_blocking(move || connect::open(row, column, &o))
+                    .await
+                    .map_err(|e| RemoteError::new(&e.to_string()))??
+            }
+            None => {
+                let (host, port) = options.endpoint(row, column);
+                let connecting = tokio::net::TcpStream::connect(join_host_port(&host, port));
+                timeout(options.timeouts.connect, connecting)
+                    .await
+                    .map_err(|_| timed_out("connection"))??
+                    .into_std()?
+            }
+        };
+        tcp.set_nonblocking(true)?;
+        let fd = AsyncFd::new(tcp.try_clone()?)?;
+        let mut session = Session::new().unwrap();
+        session.set_tcp_stream(tcp);
+        session.set_blocking(false);
+        let handshake = async {
+            loop {
+                match session.handshake() {
+                    Ok(()) => return Ok(()),
+                    Err(ref e) if e.code() == LIBSSH2_ERROR_EAGAIN => wait(&session, &fd).await?,
+                    Err(e) => return Err(RemoteError::from(e)),
+                }
+            }
+        };
+        timeout(options.timeouts.handshake, handshake)
+            .await
+            .map_err(|_| timed_out("handshake"))??;
+        connect::verify(&session, row, column, options)?;
+        let client = AsyncClient {
+            row: row,
+            column: column,
+            session: session,
+            fd: fd,
+        };
+        timeout(options.timeouts.handshake, client.authenticate(options))
+            .await
+            .map_err(|_| timed_out("authentication"))??;
+        Ok(client)
+    }
+
+    async fn retry<T, F>(&self, mut f: F) -> Result<T, RemoteError>
+    where
+        F: FnMut(&Session) -> Result<T, ssh2::Error>,
+    {
+        loop {
+            match f(&self.session) {
+                Ok(value) => return Ok(value),
+                Err(ref e) if e.code() == LIBSSH2_ERROR_EAGAIN => {
+                    wait(&self.session, &self.fd).await?
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn retry_io<T, F>(&self, mut f: F) -> Result<T, RemoteError>
+    where
+        F: FnMut() -> io::Result<T>,
+    {
+        loop {
+            match f() {
+                Ok(value) => return Ok(value),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    wait(&self.session, &self.fd).await?
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn authenticate(&self, options: &ConnectOptions) -> Result<(), RemoteError> {
+        let user = options.user();
+        if let Some(identity) = &options.identity_file {
+            let authenticated = self
+                .retry(|s| s.userauth_pubkey_file(user, None, identity, None))
+                .await;
+            if authenticated.is_ok() {
+                return Ok(());
+            }
+        }
+        self.retry(|s| s.userauth_password(user, PASSWORD)).await
+    }
+
+    async fn close(&self, channel: &mut Channel) -> Result<(), RemoteError> {
+        self.retry(|_| channel.wait_close()).await
+    }
+
+    pub async fn run(&self, command: &str, options: &RunOptions) -> Result<Output, RemoteError> {
+        for (name, _) in &options.env {
+            if !is_env_name(name) {
+                return Err(RemoteError::new(&format!(
+                    "invalid environment variable name: {}",
+                    name
+                )));
+            }
+        }
+        let mut channel = self.retry(|s| s.channel_session()).await?;
+        let command = options.wrap(command, &options.env);
+        let mut pty = options.pty;
+        if let Some(escalation) = &options.escalation {
+            if escalation.needs_pty() && pty.is_none() {
+                pty = Some((80, 24));
+            }
+        }
+        if let Some((width, height)) = pty {
+            self.retry(|_| {
+                channel.request_pty("xterm", None, Some((width as u32, height as u32, 0, 0)))
+            })
+            .await?;
+        }
+        self.retry(|_| channel.exec(&command)).await?;
+        let input = options.input.as_ref().map(|i| i.as_slice()).unwrap_or(&[]);
+        let escalation = options.escalation.as_ref().map(Escalation::new);
+        let (stdout, stderr) = self.pump(&mut channel, input, escalation).await?;
+        self.close(&mut channel).await?;
+        Ok(Output {
+            stdout: String::from_utf8(stdout)?,
+            stderr: String::from_utf8(stderr)?,
+            status: channel.exit_status()?,
+        })
+    }
+
+    async fn pump(
+        &self,
+        channel: &mut Channel,
+        input: &[u8],
+        escalation: Option<Escalation<'_>>,
+    ) -> Result<(Vec<u8>, Vec<u8>), RemoteError> {
+        let mut pump = Pump::new(input, escalation);
+        loop {
+            match pump.step(channel)? {
+                Step::Done(stdout, stderr) => return Ok((stdout, stderr)),
+                Step::Idle => wait(&self.session, &self.fd).await?,
+                Step::Busy => {}
+            }
+        }
+    }
+
+    pub async fn send(&self, filename: String) -> Result<(), RemoteError> {
+        let buffer = read(&filename)?;
+        let path = send_destination(&filename);
+        let mut remote_file = self
+            .retry(|s| s.scp_send(Path::new(&path), 0o644, buffer.len() as u64, None))
+            .await?;
+        let mut written = 0;
+        while written < buffer.len() {
+            written += self
+                .retry_io(|| remote_file.write(&buffer[written..]))
+                .await?;
+        }
+        self.retry(|_| remote_file.send_eof()).await?;
+        self.retry(|_| remote_file.wait_eof()).await?;
+        self.close(&mut remote_file).await
+    }
+
+    pub async fn recv(&self, filename: String) -> Result<(), RemoteError> {
+        let (mut remote_file, stat) = self.retry(|s| s.scp_recv(Path::new(&filename))).await?;
+        let size = stat.size() as usize;
+        let mut buffer = vec![0u8; size];
+        let mut received = 0;
+        while received < size {
+            let n = self
+                .retry_io(|| remote_file.read(&mut buffer[received..]))
+                .await?;
+            if n == 0 {
+                return Err(RemoteError::new("unexpected end of file"));
+            }
+            received += n;
+        }
+        write(recv_destination(&filename, self.row, self.column), buffer)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub enum Task {
+    Run(String, RunOptions),
+    Send(String),
+    Recv(String),
+}
+
+impl AsyncClient {
+    pub async fn perform(&self, task: Task) -> Result<Option<Output>, RemoteError> {
+        match task {
+            Task::Run(command, options) => self.run(&command, &options).await.map(Some),
+            Task::Send(filename) => self.send(filename).await.map(|_| None),
+            Task::Recv(filename) => self.recv(filename).await.map(|_| None),
+        }
+    }
+}
+
+pub struct Engine {
+    threads: usize,
+    concurrency: usize,
+}
+
+impl Engine {
+    pub fn new(threads: usize, concurrency: usize) -> Engine {
+        Engine {
+            threads: threads.max(1),
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    pub fn execute<P, T, F, Fut>(
+        &self,
+        targets: Vec<(u8, u8, ConnectOptions, P)>,
+        operation: F,
+    ) -> Vec<(u8, u8, Result<T, RemoteError>)>
+    where
+        P: Send + 'static,
+        F: Fn(AsyncClient, P) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = Result<T, RemoteError>> + 'static,
+        T: Send + 'static,
+    {
+        let mut shards: Vec<Shard<P>> = (0..self.threads).map(|_| Vec::new()).collect();
+        for (i, (row, column, options, payload)) in targets.into_iter().enumerate() {
+            shards[i % self.threads].push((i, row, column, options, payload));
+        }
+        let permits = self.concurrency.div_ceil(self.threads);
+        let handles: Vec<_> = shards
+            .into_iter()
+            .filter(|shard| !shard.is_empty())
+            .map(|shard| {
+                let operation = operation.clone();
+                thread::spawn(move || shard_run(shard, permits, operation))
+            })
+            .collect();
+        let mut results: Vec<(usize, u8, u8, Result<T, RemoteError>)> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        results.sort_by_key(|(i, _, _, _)| *i);
+        results
+            .into_iter()
+            .map(|(_, row, column, result)| (row, column, result))
+            .collect()
+    }
+
+    pub fn perform(
+        &self,
+        targets: Vec<(u8, u8, ConnectOptions, Task)>,
+    ) -> Vec<(u8, u8, Result<Option<Output>, RemoteError>)> {
+        self.execute(
+            targets,
+            |client, task| async move { client.perform(task).await },
+        )
+    }
+}
+
+fn shard_run<P, T, F, Fut>(
+    shard: Shard<P>,
+    permits: usize,
+    operation: F,
+) -> Vec<(usize, u8, u8, Result<T, RemoteError>)>
+where
+    P: 'static,
+    F: Fn(AsyncClient, P) -> Fut + Clone + 'static,
+    Fut: Future<Output = Result<T, RemoteError>> + 'static,
+    T: 'static,
+{
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    let local = LocalSet::new();
+    let semaphore = Arc::new(Semaphore::new(permits));
+    local.block_on(&runtime, async move {
+        let tasks: Vec<_> = shard
+            .into_iter()
+            .map(|(i, row, column, options, payload)| {
+                let semaphore = semaphore.clone();
+                let operation = operation.clone();
+                let task = task::spawn_local(async move {
+                    let _permit = semaphore.acquire().await.unwrap();
+                    let client = AsyncClient::connect(row, column, &options).await?;
+                    operation(client, payload).await
+                });
+                (i, row, column, task)
+            })
+            .collect();
+        let mut results = Vec::new();
+        for (i, row, column, task) in tasks {
+            let result = match task.await {
+                Ok(result) => result,
+                Err(e) => Err(RemoteError::new(&e.to_string())),
+            };
+            results.push((i, row, column, result));
+        }
+        results
+    })
+}
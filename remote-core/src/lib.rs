@@ -1,13 +1,139 @@
+#[cfg(feature = "like")]
 use cookies_loader::*;
 use error::RemoteError;
+#[cfg(feature = "like")]
 use liker::like;
-use ssh2::Session;
-use std::fs::{read, write};
+use ssh2::{Channel, ErrorCode, Session};
+use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::net::TcpStream;
 use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "like")]
 use tempfile;
 
+mod connect;
+#[cfg(feature = "async")]
+pub mod engine;
+pub mod escape;
+mod local;
+mod observer;
+mod pool;
+mod privilege;
+mod proxy;
+mod sshconfig;
+mod transport;
+
+pub use connect::{join_host_port, probe, ConnectOptions, Probe, Timeouts};
+use escape::{escape, is_env_name};
+pub use local::LocalHost;
+pub use observer::Observer;
+pub use pool::SessionPool;
+use privilege::Escalation;
+pub use privilege::{Become, BecomeMethod};
+pub use proxy::Proxy;
+pub use sshconfig::{HostSettings, SshConfig};
+pub use transport::{Transport, TransportKind};
+
+const LIBSSH2_ERROR_EAGAIN: ErrorCode = ErrorCode::Session(-37);
+
+pub const USERNAME: &str = "ioi";
+pub const AUTH_METHOD: &str = "password";
+const PASSWORD: &str = "ioi";
+
+pub fn host(row: u8, column: u8) -> String {
+    format!("fdcd::c:{}:{}", row, column)
+}
+
+pub fn address(row: u8, column: u8) -> String {
+    connect::join_host_port(&host(row, column), 22)
+}
+
+pub(crate) fn copy<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    observer: &dyn Observer,
+) -> Result<u64, RemoteError> {
+    let mut buffer = [0u8; 32768];
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        writer.write_all(&buffer[..n])?;
+        total += n as u64;
+        observer.transferred(n as u64);
+    }
+}
+
+pub fn send_destination(filename: &str) -> String {
+    Path::new(filename)
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn recv_destination(filename: &str, row: u8, column: u8) -> String {
+    let path = Path::new(filename);
+    format!(
+        "{}_{}{}.{}",
+        path.file_stem().unwrap().to_str().unwrap(),
+        (64 + row) as char,
+        column,
+        match path.extension() {
+            Some(ext) => ext.to_str().unwrap(),
+            None => "",
+        }
+    )
+}
+
+pub enum ShellInput {
+    Data(Vec<u8>),
+    Resize(u16, u16),
+}
+
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    pub env: Vec<(String, String)>,
+    pub cwd: Option<String>,
+    pub pty: Option<(u16, u16)>,
+    pub input: Option<Arc<Vec<u8>>>,
+    pub escalation: Option<Become>,
+}
+
+impl RunOptions {
+    pub fn command_line(&self, command: &str) -> String {
+        self.wrap(command, &self.env)
+    }
+
+    fn wrap(&self, command: &str, exports: &[(String, String)]) -> String {
+        let mut prefix = String::new();
+        for (name, value) in exports {
+            prefix.push_str(&format!("export {}={}; ", name, escape(value)));
+        }
+        if let Some(cwd) = &self.cwd {
+            prefix.push_str(&format!("cd {} || exit 1; ", escape(cwd)));
+        }
+        match &self.escalation {
+            Some(escalation) => escalation.wrap(&(prefix + command)),
+            None => prefix + command,
+        }
+    }
+}
+
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
+}
+
 pub struct OlinfoClient {
     row: u8,
     column: u8,
@@ -16,14 +142,20 @@ pub struct OlinfoClient {
 
 impl OlinfoClient {
     pub fn new(row: u8, column: u8) -> Result<OlinfoClient, RemoteError> {
-        let username: &str = "ioi";
-        let password: &str = "ioi";
-        let addr: String = format!("[fdcd::c:{}:{}]:22", row, column);
-        let tcp = TcpStream::connect(addr)?;
-        let mut sess = Session::new().unwrap();
-        sess.set_tcp_stream(tcp);
-        sess.handshake()?;
-        sess.userauth_password(username, password)?;
+        OlinfoClient::connect(row, column, &ConnectOptions::default(), &())
+    }
+
+    pub fn connect(
+        row: u8,
+        column: u8,
+        options: &ConnectOptions,
+        observer: &dyn Observer,
+    ) -> Result<OlinfoClient, RemoteError> {
+        let tcp = connect::open(row, column, options)?;
+        let sess = connect::handshake(tcp, &options.timeouts)?;
+        connect::verify(&sess, row, column, options)?;
+        observer.authenticating();
+        connect::authenticate(&sess, options)?;
         Ok(OlinfoClient {
             row: row,
             column: column,
@@ -31,46 +163,107 @@ impl OlinfoClient {
         })
     }
 
-    pub fn run(&self, command: String) -> Result<(String, String), RemoteError> {
+    pub fn set_keepalive(&self, interval: u32) {
+        self.session.set_keepalive(true, interval);
+    }
+
+    pub fn keepalive_send(&self) -> Result<u32, RemoteError> {
+        Ok(self.session.keepalive_send()?)
+    }
+
+    pub fn run(&self, command: &str, options: &RunOptions) -> Result<Output, RemoteError> {
         let mut channel = self.session.channel_session()?;
+        let mut exports = Vec::new();
+        for (name, value) in &options.env {
+            if !is_env_name(name) {
+                return Err(RemoteError::new(&format!(
+                    "invalid environment variable name: {}",
+                    name
+                )));
+            }
+            if options.escalation.is_some() || channel.setenv(name, value).is_err() {
+                exports.push((name.clone(), value.clone()));
+            }
+        }
+        let command = options.wrap(command, &exports);
+        let mut pty = options.pty;
+        if let Some(escalation) = &options.escalation {
+            if escalation.needs_pty() && pty.is_none() {
+                pty = Some((80, 24));
+            }
+        }
+        if let Some((width, height)) = pty {
+            channel.request_pty("xterm", None, Some((width as u32, height as u32, 0, 0)))?;
+        }
         channel.exec(&command)?;
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-        channel.read_to_string(&mut stdout)?;
-        channel.stderr().read_to_string(&mut stderr)?;
+        let (stdout, stderr) = match (&options.input, &options.escalation) {
+            (None, None) => {
+                let mut stdout = String::new();
+                let mut stderr = String::new();
+                channel.read_to_string(&mut stdout)?;
+                channel.stderr().read_to_string(&mut stderr)?;
+                (stdout, stderr)
+            }
+            (input, escalation) => {
+                let input = input.as_ref().map(|i| i.as_slice()).unwrap_or(&[]);
+                self.session.set_blocking(false);
+                let result = pump_exec(
+                    &mut channel,
+                    input,
+                    escalation.as_ref().map(Escalation::new),
+                );
+                self.session.set_blocking(true);
+                let (stdout, stderr) = result?;
+                (String::from_utf8(stdout)?, String::from_utf8(stderr)?)
+            }
+        };
         channel.wait_close()?;
-        Ok((stdout, stderr))
+        Ok(Output {
+            stdout: stdout,
+            stderr: stderr,
+            status: channel.exit_status()?,
+        })
     }
 
-    pub fn send(&self, filename: String) -> Result<(), RemoteError> {
-        let buffer = read(&filename)?;
-        let path = Path::new(Path::new(&filename).file_name().unwrap());
-        let mut remote_file = self
-            .session
-            .scp_send(path, 0o644, buffer.len() as u64, None)?;
-        remote_file.write_all(&buffer)?;
+    pub fn shell(
+        &self,
+        size: (u16, u16),
+        input: Receiver<ShellInput>,
+        output: Sender<Vec<u8>>,
+    ) -> Result<i32, RemoteError> {
+        let mut channel = self.session.channel_session()?;
+        channel.request_pty("xterm", None, Some((size.0 as u32, size.1 as u32, 0, 0)))?;
+        channel.shell()?;
+        self.session.set_blocking(false);
+        let result = pump_shell(&mut channel, input, output);
+        self.session.set_blocking(true);
+        result?;
+        channel.wait_close()?;
+        Ok(channel.exit_status()?)
+    }
+
+    pub fn send(&self, filename: String, observer: &dyn Observer) -> Result<(), RemoteError> {
+        let mut file = File::open(&filename)?;
+        let path = send_destination(&filename);
+        let mut remote_file =
+            self.session
+                .scp_send(Path::new(&path), 0o644, file.metadata()?.len(), None)?;
+        copy(&mut file, &mut remote_file, observer)?;
+        remote_file.send_eof()?;
+        remote_file.wait_eof()?;
+        remote_file.close()?;
+        remote_file.wait_close()?;
         Ok(())
     }
 
-    pub fn recv(&self, filename: String) -> Result<(), RemoteError> {
-        let path = Path::new(&filename);
-        let (mut remote_file, _) = self.session.scp_recv(path)?;
-        let mut buffer = Vec::new();
-        remote_file.read_to_end(&mut buffer)?;
-        let local_file = format!(
-            "{}_{}{}.{}",
-            path.file_stem().unwrap().to_str().unwrap(),
-            (64 + self.row) as char,
-            self.column,
-            match path.extension() {
-                Some(ext) => ext.to_str().unwrap(),
-                None => "",
-            }
-        );
-        write(&local_file, buffer)?;
+    pub fn recv(&self, filename: String, observer: &dyn Observer) -> Result<(), RemoteError> {
+        let (mut remote_file, _) = self.session.scp_recv(Path::new(&filename))?;
+        let mut file = File::create(recv_destination(&filename, self.row, self.column))?;
+        copy(&mut remote_file, &mut file, observer)?;
         Ok(())
     }
 
+    #[cfg(feature = "like")]
     pub fn like(&self, user: String) -> Result<(String, String), RemoteError> {
         {
             let mut tmpfile = tempfile::Builder::new().suffix(".sqlite").tempfile()?;
@@ -87,9 +280,14 @@ impl OlinfoClient {
         {
             let mut tmpfile = tempfile::Builder::new().suffix(".sqlite").tempfile()?;
             let mut buffer = Vec::new();
-            let firefox_match = self.run("ls .mozilla/firefox/*/cookies.sqlite | head -1".to_string())?.0;
+            let firefox_match = self
+                .run(
+                    "ls .mozilla/firefox/*/cookies.sqlite | head -1",
+                    &RunOptions::default(),
+                )?
+                .stdout;
             let firefox_path = Path::new(firefox_match.trim());
-            if let Ok((mut remote_file, _)) = self.session.scp_recv(&firefox_path) {
+            if let Ok((mut remote_file, _)) = self.session.scp_recv(firefox_path) {
                 remote_file.read_to_end(&mut buffer)?;
                 tmpfile.write_all(&buffer)?;
                 if let Some(token) = get_firefox_cookie(tmpfile.path(), ".olinfo.it", "token") {
@@ -100,3 +298,177 @@ impl OlinfoClient {
         Err(RemoteError::new("cookie not found"))
     }
 }
+
+pub(crate) enum Step {
+    Busy,
+    Idle,
+    Done(Vec<u8>, Vec<u8>),
+}
+
+pub(crate) struct Pump<'a> {
+    input: &'a [u8],
+    escalation: Option<Escalation<'a>>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    secret: Vec<u8>,
+    written: usize,
+    eof_sent: bool,
+}
+
+impl<'a> Pump<'a> {
+    pub(crate) fn new(input: &'a [u8], escalation: Option<Escalation<'a>>) -> Pump<'a> {
+        Pump {
+            input: input,
+            escalation: escalation,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            secret: Vec::new(),
+            written: 0,
+            eof_sent: false,
+        }
+    }
+
+    pub(crate) fn step(&mut self, channel: &mut Channel) -> Result<Step, RemoteError> {
+        let mut buffer = [0u8; 16384];
+        let mut idle = true;
+        let authenticated = self.escalation.as_ref().is_none_or(|e| e.done());
+        if !self.secret.is_empty() {
+            match channel.write(&self.secret) {
+                Ok(n) => {
+                    idle = false;
+                    self.secret.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if authenticated && self.written < self.input.len() {
+            match channel.write(&self.input[self.written..]) {
+                Ok(n) => {
+                    idle = false;
+                    self.written += n;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if authenticated && !self.eof_sent {
+            match channel.send_eof() {
+                Ok(()) => self.eof_sent = true,
+                Err(ref e) if e.code() == LIBSSH2_ERROR_EAGAIN => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let mut drained = true;
+        for is_stdout in &[true, false] {
+            let read = if *is_stdout {
+                channel.read(&mut buffer)
+            } else {
+                channel.stderr().read(&mut buffer)
+            };
+            let output = if *is_stdout {
+                &mut self.stdout
+            } else {
+                &mut self.stderr
+            };
+            match read {
+                Ok(0) => {}
+                Ok(n) => {
+                    idle = false;
+                    drained = false;
+                    match &mut self.escalation {
+                        Some(e) => {
+                            if let Some(password) = e.feed(&buffer[..n], *is_stdout, output)? {
+                                self.secret.extend(password);
+                            }
+                        }
+                        None => output.extend(&buffer[..n]),
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => drained = false,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if drained && channel.eof() {
+            return match self.escalation {
+                Some(ref e) if !e.done() => Err(e.denied()),
+                _ => Ok(Step::Done(
+                    std::mem::take(&mut self.stdout),
+                    std::mem::take(&mut self.stderr),
+                )),
+            };
+        }
+        Ok(if idle { Step::Idle } else { Step::Busy })
+    }
+}
+
+fn pump_exec(
+    channel: &mut Channel,
+    input: &[u8],
+    escalation: Option<Escalation>,
+) -> Result<(Vec<u8>, Vec<u8>), RemoteError> {
+    let mut pump = Pump::new(input, escalation);
+    loop {
+        match pump.step(channel)? {
+            Step::Done(stdout, stderr) => return Ok((stdout, stderr)),
+            Step::Idle => thread::sleep(Duration::from_millis(10)),
+            Step::Busy => {}
+        }
+    }
+}
+
+fn pump_shell(
+    channel: &mut Channel,
+    input: Receiver<ShellInput>,
+    output: Sender<Vec<u8>>,
+) -> Result<(), RemoteError> {
+    let mut buffer = [0u8; 4096];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut closing = false;
+    let mut eof_sent = false;
+    loop {
+        let mut idle = true;
+        match channel.read(&mut buffer) {
+            Ok(0) => {
+                if channel.eof() {
+                    return Ok(());
+                }
+            }
+            Ok(n) => {
+                idle = false;
+                if output.send(buffer[..n].to_vec()).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.into()),
+        }
+        while !closing {
+            match input.try_recv() {
+                Ok(ShellInput::Data(data)) => pending.extend(data),
+                Ok(ShellInput::Resize(width, height)) => {
+                    let _ = channel.request_pty_size(width as u32, height as u32, None, None);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => closing = true,
+            }
+        }
+        if !pending.is_empty() {
+            match channel.write(&pending) {
+                Ok(n) => {
+                    idle = false;
+                    pending.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if closing && !eof_sent {
+            match channel.send_eof() {
+                Ok(()) => eof_sent = true,
+                Err(ref e) if e.code() == LIBSSH2_ERROR_EAGAIN => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if idle {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::transport::Transport;
+use crate::{copy, recv_destination, send_destination, Observer, Output, RunOptions};
+use error::RemoteError;
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+pub struct LocalHost {
+    row: u8,
+    column: u8,
+    sandbox: PathBuf,
+}
+
+impl LocalHost {
+    pub fn new(root: &Path, row: u8, column: u8) -> Result<LocalHost, RemoteError> {
+        let sandbox = root.join(format!("{}{}", (64 + row) as char, column));
+        create_dir_all(&sandbox)?;
+        Ok(LocalHost {
+            row: row,
+            column: column,
+            sandbox: sandbox,
+        })
+    }
+
+    fn path(&self, filename: &str) -> PathBuf {
+        self.sandbox.join(filename.trim_start_matches('/'))
+    }
+}
+
+impl Transport for LocalHost {
+    fn exec(&self, command: &str, options: &RunOptions) -> Result<Output, RemoteError> {
+        if options.escalation.is_some() {
+            return Err(RemoteError::new(
+                "privilege escalation is not supported by the local transport",
+            ));
+        }
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(options.command_line(command))
+            .current_dir(&self.sandbox)
+            .env("HOME", &self.sandbox)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let input = options.input.clone();
+        let writer = thread::spawn(move || {
+            if let Some(input) = input {
+                let _ = stdin.write_all(&input);
+            }
+        });
+        let output = child.wait_with_output()?;
+        writer.join().unwrap();
+        Ok(Output {
+            stdout: String::from_utf8(output.stdout)?,
+            stderr: String::from_utf8(output.stderr)?,
+            status: output.status.code().unwrap_or(-1),
+        })
+    }
+
+    fn upload(&self, filename: &str, observer: &dyn Observer) -> Result<(), RemoteError> {
+        copy(
+            &mut File::open(filename)?,
+            &mut File::create(self.path(&send_destination(filename)))?,
+            observer,
+        )?;
+        Ok(())
+    }
+
+    fn download(&self, filename: &str, observer: &dyn Observer) -> Result<(), RemoteError> {
+        copy(
+            &mut File::open(self.path(filename))?,
+            &mut File::create(recv_destination(filename, self.row, self.column))?,
+            observer,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,7 @@
+pub trait Observer {
+    fn authenticating(&self) {}
+
+    fn transferred(&self, _bytes: u64) {}
+}
+
+impl Observer for () {}
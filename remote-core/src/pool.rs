@@ -0,0 +1,83 @@
+use crate::{Observer, Transport};
+use error::RemoteError;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+type Slot = Arc<Mutex<Option<Box<dyn Transport>>>>;
+type Connector =
+    Box<dyn Fn(u8, u8, &dyn Observer) -> Result<Box<dyn Transport>, RemoteError> + Send + Sync>;
+
+pub struct SessionPool {
+    slots: Mutex<HashMap<(u8, u8), Slot>>,
+    connector: Connector,
+}
+
+impl SessionPool {
+    pub fn new<F>(keepalive: u32, connector: F) -> Arc<SessionPool>
+    where
+        F: Fn(u8, u8, &dyn Observer) -> Result<Box<dyn Transport>, RemoteError>,
+        F: Send + Sync + 'static,
+    {
+        let pool = Arc::new(SessionPool {
+            slots: Mutex::new(HashMap::new()),
+            connector: Box::new(move |row, column, observer| {
+                let client = connector(row, column, observer)?;
+                if keepalive > 0 {
+                    client.set_keepalive(keepalive);
+                }
+                Ok(client)
+            }),
+        });
+        if keepalive > 0 {
+            let weak = Arc::downgrade(&pool);
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(keepalive as u64));
+                match weak.upgrade() {
+                    Some(pool) => pool.send_keepalives(),
+                    None => break,
+                }
+            });
+        }
+        pool
+    }
+
+    pub fn with<T, F>(
+        &self,
+        row: u8,
+        column: u8,
+        observer: &dyn Observer,
+        f: F,
+    ) -> Result<T, RemoteError>
+    where
+        F: FnOnce(&dyn Transport) -> Result<T, RemoteError>,
+    {
+        let slot = self.slot(row, column);
+        let mut client = slot.lock().unwrap();
+        if client.is_none() {
+            *client = Some((self.connector)(row, column, observer)?);
+        }
+        f(client.as_ref().unwrap().as_ref())
+    }
+
+    fn slot(&self, row: u8, column: u8) -> Slot {
+        self.slots
+            .lock()
+            .unwrap()
+            .entry((row, column))
+            .or_insert_with(|| Arc::new(Mutex::new(None)))
+            .clone()
+    }
+
+    fn send_keepalives(&self) {
+        let slots: Vec<Slot> = self.slots.lock().unwrap().values().cloned().collect();
+        for slot in slots {
+            if let Ok(client) = slot.try_lock() {
+                if let Some(client) = client.as_ref() {
+                    let _ = client.keepalive();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::escape::escape;
+use error::RemoteError;
+
+const MARKER: &str = "__REMOTE_BECOME_OK__";
+const SUDO_PROMPT: &str = "[remote-become] password: ";
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum BecomeMethod {
+    Sudo,
+    Su,
+}
+
+impl BecomeMethod {
+    pub fn parse(name: &str) -> Option<BecomeMethod> {
+        match name {
+            "sudo" => Some(BecomeMethod::Sudo),
+            "su" => Some(BecomeMethod::Su),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            BecomeMethod::Sudo => "sudo",
+            BecomeMethod::Su => "su",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Become {
+    pub method: BecomeMethod,
+    pub user: String,
+    pub password: Option<String>,
+}
+
+impl Become {
+    pub(crate) fn wrap(&self, command: &str) -> String {
+        let inner = format!("echo {}; {}", MARKER, command);
+        match self.method {
+            BecomeMethod::Sudo => format!(
+                "sudo {} -u {} -- sh -c {}",
+                match self.password {
+                    Some(_) => format!("-S -p {}", escape(SUDO_PROMPT)),
+                    None => "-n".to_string(),
+                },
+                escape(&self.user),
+                escape(&inner)
+            ),
+            BecomeMethod::Su => format!("su {} -c {}", escape(&self.user), escape(&inner)),
+        }
+    }
+
+    pub(crate) fn needs_pty(&self) -> bool {
+        self.method == BecomeMethod::Su
+    }
+}
+
+pub(crate) struct Escalation<'a> {
+    config: &'a Become,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    prompts: u32,
+    done: bool,
+}
+
+impl<'a> Escalation<'a> {
+    pub(crate) fn new(config: &'a Become) -> Escalation<'a> {
+        Escalation {
+            config: config,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            prompts: 0,
+            done: false,
+        }
+    }
+
+    pub(crate) fn done(&self) -> bool {
+        self.done
+    }
+
+    pub(crate) fn feed(
+        &mut self,
+        data: &[u8],
+        is_stdout: bool,
+        output: &mut Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, RemoteError> {
+        if self.done {
+            output.extend(data);
+            return Ok(None);
+        }
+        let buffer = if is_stdout {
+            &mut self.stdout
+        } else {
+            &mut self.stderr
+        };
+        buffer.extend(data);
+        if is_stdout {
+            if let Some(start) = find(buffer, MARKER.as_bytes()) {
+                let mut end = start + MARKER.len();
+                while end < buffer.len() && (buffer[end] == b'\r' || buffer[end] == b'\n') {
+                    end += 1;
+                }
+                output.extend(&buffer[end..]);
+                self.done = true;
+                return Ok(None);
+            }
+        }
+        if !self.prompted(is_stdout) {
+            return Ok(None);
+        }
+        self.stdout.clear();
+        self.stderr.clear();
+        self.prompts += 1;
+        match &self.config.password {
+            None => Err(RemoteError::new(&format!(
+                "{}: a password is required",
+                self.config.method.name()
+            ))),
+            Some(_) if self.prompts > 1 => Err(RemoteError::new(&format!(
+                "{}: incorrect password",
+                self.config.method.name()
+            ))),
+            Some(password) => Ok(Some(format!("{}\n", password).into_bytes())),
+        }
+    }
+
+    fn prompted(&self, is_stdout: bool) -> bool {
+        let buffer = if is_stdout {
+            &self.stdout
+        } else {
+            &self.stderr
+        };
+        let text = String::from_utf8_lossy(buffer);
+        match self.config.method {
+            BecomeMethod::Sudo => text.ends_with(SUDO_PROMPT),
+            BecomeMethod::Su => text.trim_end().ends_with("assword:"),
+        }
+    }
+
+    pub(crate) fn denied(&self) -> RemoteError {
+        let stderr = String::from_utf8_lossy(&self.stderr);
+        let stdout = String::from_utf8_lossy(&self.stdout);
+        let reason = match stderr.trim() {
+            "" => stdout.trim().to_string(),
+            reason => reason.to_string(),
+        };
+        RemoteError::new(&format!(
+            "{} denied: {}",
+            self.config.method.name(),
+            match reason.as_str() {
+                "" => "no reason given",
+                reason => reason,
+            }
+        ))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,227 @@
+use crate::connect::{self, Timeouts};
+use crate::sshconfig::SshConfig;
+use crate::LIBSSH2_ERROR_EAGAIN;
+use error::RemoteError;
+use ssh2::Channel;
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, PartialEq)]
+pub enum Proxy {
+    Jump(String),
+    Command(String),
+}
+
+struct Jump {
+    user: String,
+    host: String,
+    port: u16,
+    identity_file: Option<PathBuf>,
+}
+
+impl Jump {
+    fn parse(spec: &str) -> Result<Jump, RemoteError> {
+        let invalid = || RemoteError::new(&format!("invalid jump host: {}", spec));
+        if spec.contains(',') {
+            return Err(RemoteError::new(&format!(
+                "multiple jump hosts are not supported: {}",
+                spec
+            )));
+        }
+        let (user, rest) = match spec.rfind('@') {
+            Some(at) => (Some(spec[..at].to_string()), &spec[at + 1..]),
+            None => (None, spec),
+        };
+        let (host, port) = if rest.starts_with('[') {
+            let end = rest.find(']').ok_or_else(invalid)?;
+            (&rest[1..end], rest[end + 1..].trim_start_matches(':'))
+        } else {
+            match rest.find(':') {
+                Some(colon) => (&rest[..colon], &rest[colon + 1..]),
+                None => (rest, ""),
+            }
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let settings = SshConfig::load()?.lookup(&[host]);
+        Ok(Jump {
+            user: user
+                .or(settings.user)
+                .unwrap_or_else(|| env::var("USER").unwrap_or_default()),
+            host: settings.hostname.unwrap_or_else(|| host.to_string()),
+            port: match port {
+                "" => settings.port.unwrap_or(22),
+                port => port.parse().map_err(|_| invalid())?,
+            },
+            identity_file: settings.identity_file,
+        })
+    }
+}
+
+pub(crate) fn open(
+    proxy: &Proxy,
+    host: &str,
+    port: u16,
+    user: &str,
+    timeouts: &Timeouts,
+) -> Result<TcpStream, RemoteError> {
+    match proxy {
+        Proxy::Jump(spec) => jump(&Jump::parse(spec)?, host, port, timeouts),
+        Proxy::Command(command) => command_stream(&expand(command, host, port, user)),
+    }
+}
+
+fn pair() -> Result<(TcpStream, TcpStream), RemoteError> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let client = TcpStream::connect(listener.local_addr()?)?;
+    let (server, _) = listener.accept()?;
+    Ok((client, server))
+}
+
+fn jump(
+    bastion: &Jump,
+    host: &str,
+    port: u16,
+    timeouts: &Timeouts,
+) -> Result<TcpStream, RemoteError> {
+    let tcp = connect::open_address(
+        &connect::join_host_port(&bastion.host, bastion.port),
+        timeouts,
+    )?;
+    let session = connect::handshake(tcp, timeouts)?;
+    let authenticated = match &bastion.identity_file {
+        Some(identity) => session.userauth_pubkey_file(&bastion.user, None, identity, None),
+        None => session.userauth_agent(&bastion.user),
+    };
+    authenticated.map_err(|e| RemoteError::new(&format!("jump host {}: {}", bastion.host, e)))?;
+    session.set_timeout(0);
+    let mut channel = session.channel_direct_tcpip(host, port, None)?;
+    let (client, server) = pair()?;
+    thread::spawn(move || {
+        server.set_nonblocking(true).unwrap();
+        session.set_blocking(false);
+        let _ = tunnel(&mut channel, server);
+        drop(session);
+    });
+    Ok(client)
+}
+
+fn tunnel(channel: &mut Channel, mut socket: TcpStream) -> Result<(), RemoteError> {
+    let mut buffer = [0u8; 16384];
+    let mut upstream: Vec<u8> = Vec::new();
+    let mut downstream: Vec<u8> = Vec::new();
+    let mut closing = false;
+    let mut eof_sent = false;
+    loop {
+        let mut idle = true;
+        if !closing && upstream.is_empty() {
+            match socket.read(&mut buffer) {
+                Ok(0) => closing = true,
+                Ok(n) => {
+                    idle = false;
+                    upstream.extend(&buffer[..n]);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !upstream.is_empty() {
+            match channel.write(&upstream) {
+                Ok(n) => {
+                    idle = false;
+                    upstream.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else if closing && !eof_sent {
+            match channel.send_eof() {
+                Ok(()) => eof_sent = true,
+                Err(ref e) if e.code() == LIBSSH2_ERROR_EAGAIN => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if downstream.is_empty() {
+            match channel.read(&mut buffer) {
+                Ok(0) => {
+                    if channel.eof() {
+                        let _ = socket.shutdown(Shutdown::Write);
+                        return Ok(());
+                    }
+                }
+                Ok(n) => {
+                    idle = false;
+                    downstream.extend(&buffer[..n]);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !downstream.is_empty() {
+            match socket.write(&downstream) {
+                Ok(n) => {
+                    idle = false;
+                    downstream.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if idle {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+fn expand(command: &str, host: &str, port: u16, user: &str) -> String {
+    let mut expanded = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => expanded.push_str(host),
+            Some('p') => expanded.push_str(&port.to_string()),
+            Some('r') => expanded.push_str(user),
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+fn command_stream(command: &str) -> Result<TcpStream, RemoteError> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let (client, server) = pair()?;
+    let mut reader = server.try_clone()?;
+    let mut writer = server;
+    thread::spawn(move || {
+        let _ = io::copy(&mut reader, &mut stdin);
+    });
+    thread::spawn(move || {
+        let _ = io::copy(&mut stdout, &mut writer);
+        let _ = writer.shutdown(Shutdown::Both);
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+    Ok(client)
+}
@@ -0,0 +1,245 @@
+use crate::proxy::Proxy;
+use error::RemoteError;
+use std::env;
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+struct Block {
+    patterns: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+pub struct SshConfig {
+    blocks: Vec<Block>,
+}
+
+#[derive(Clone, Default)]
+pub struct HostSettings {
+    pub hostname: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub identity_file: Option<PathBuf>,
+    pub known_hosts: Option<PathBuf>,
+    pub proxy: Option<Proxy>,
+    pub connect_timeout: Option<u64>,
+}
+
+fn home() -> PathBuf {
+    PathBuf::from(env::var_os("HOME").unwrap_or_default())
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    if path == "~" {
+        home()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        home().join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+fn wildcard(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard(&pattern[1..], text) || (!text.is_empty() && wildcard(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard(&pattern[1..], &text[1..]),
+        (Some(p), Some(t)) => p == t && wildcard(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+fn split(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (key, rest) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+        Some(i) => (&line[..i], &line[i..]),
+        None => (line, ""),
+    };
+    let rest = rest.trim_start();
+    let rest = rest.trim_start_matches('=');
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    args.push(current.clone());
+                    current.clear();
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        args.push(current);
+    }
+    Some((key.to_lowercase(), args))
+}
+
+fn glob(pattern: &Path) -> Result<Vec<PathBuf>, RemoteError> {
+    let name = pattern
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if !name.contains(['*', '?']) {
+        return Ok(if pattern.exists() {
+            vec![pattern.to_path_buf()]
+        } else {
+            Vec::new()
+        });
+    }
+    let dir = pattern.parent().unwrap_or_else(|| Path::new("."));
+    let mut paths = Vec::new();
+    match read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries {
+                let entry = entry?;
+                if wildcard(
+                    name.as_bytes(),
+                    entry.file_name().to_string_lossy().as_bytes(),
+                ) {
+                    paths.push(entry.path());
+                }
+            }
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+impl Block {
+    fn matches(&self, names: &[&str]) -> bool {
+        names.iter().any(|name| {
+            let name = name.to_lowercase();
+            let mut positive = false;
+            for pattern in &self.patterns {
+                let pattern = pattern.to_lowercase();
+                if let Some(negated) = pattern.strip_prefix('!') {
+                    if wildcard(negated.as_bytes(), name.as_bytes()) {
+                        return false;
+                    }
+                } else if wildcard(pattern.as_bytes(), name.as_bytes()) {
+                    positive = true;
+                }
+            }
+            positive
+        })
+    }
+}
+
+impl SshConfig {
+    pub fn path() -> PathBuf {
+        home().join(".ssh/config")
+    }
+
+    pub fn load() -> Result<SshConfig, RemoteError> {
+        let mut config = SshConfig::default();
+        config.parse(&SshConfig::path(), 0)?;
+        Ok(config)
+    }
+
+    fn parse(&mut self, path: &Path, depth: usize) -> Result<(), RemoteError> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(RemoteError::new(&format!(
+                "{}: too many nested includes",
+                path.display()
+            )));
+        }
+        let content = match read_to_string(path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound && depth == 0 => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for (key, args) in content.lines().filter_map(split) {
+            match key.as_str() {
+                "host" => self.blocks.push(Block {
+                    patterns: args,
+                    options: Vec::new(),
+                }),
+                "match" => self.blocks.push(Block {
+                    patterns: Vec::new(),
+                    options: Vec::new(),
+                }),
+                "include" => {
+                    for arg in args {
+                        let mut pattern = expand_home(&arg);
+                        if pattern.is_relative() {
+                            pattern = home().join(".ssh").join(pattern);
+                        }
+                        for include in glob(&pattern)? {
+                            self.parse(&include, depth + 1)?;
+                        }
+                    }
+                }
+                _ => {
+                    let value = args.join(" ");
+                    match self.blocks.last_mut() {
+                        Some(block) => block.options.push((key, value)),
+                        None => self.blocks.push(Block {
+                            patterns: vec!["*".to_string()],
+                            options: vec![(key, value)],
+                        }),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, names: &[&str]) -> HostSettings {
+        let mut settings = HostSettings::default();
+        let mut proxy = false;
+        for block in self.blocks.iter().filter(|b| b.matches(names)) {
+            for (key, value) in &block.options {
+                match key.as_str() {
+                    "hostname" if settings.hostname.is_none() => {
+                        settings.hostname = Some(value.replace("%h", names[0]).replace("%%", "%"))
+                    }
+                    "port" if settings.port.is_none() => settings.port = value.parse().ok(),
+                    "user" if settings.user.is_none() => settings.user = Some(value.clone()),
+                    "identityfile" if settings.identity_file.is_none() => {
+                        settings.identity_file = Some(expand_home(value))
+                    }
+                    "userknownhostsfile" if settings.known_hosts.is_none() => {
+                        settings.known_hosts = value.split_whitespace().next().map(expand_home)
+                    }
+                    "proxyjump" | "proxycommand" if !proxy => {
+                        proxy = true;
+                        settings.proxy = if value == "none" {
+                            None
+                        } else if key == "proxyjump" {
+                            Some(Proxy::Jump(value.clone()))
+                        } else {
+                            Some(Proxy::Command(value.clone()))
+                        };
+                    }
+                    "connecttimeout" if settings.connect_timeout.is_none() => {
+                        settings.connect_timeout = value.parse().ok()
+                    }
+                    _ => {}
+                }
+            }
+        }
+        settings
+    }
+}
@@ -0,0 +1,91 @@
+use crate::local::LocalHost;
+use crate::{ConnectOptions, Observer, OlinfoClient, Output, RunOptions, ShellInput};
+use error::RemoteError;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+
+pub trait Transport: Send {
+    fn exec(&self, command: &str, options: &RunOptions) -> Result<Output, RemoteError>;
+
+    fn upload(&self, filename: &str, observer: &dyn Observer) -> Result<(), RemoteError>;
+
+    fn download(&self, filename: &str, observer: &dyn Observer) -> Result<(), RemoteError>;
+
+    fn set_keepalive(&self, _interval: u32) {}
+
+    fn keepalive(&self) -> Result<(), RemoteError> {
+        Ok(())
+    }
+
+    fn shell(
+        &self,
+        _size: (u16, u16),
+        _input: Receiver<ShellInput>,
+        _output: Sender<Vec<u8>>,
+    ) -> Result<i32, RemoteError> {
+        Err(RemoteError::new(
+            "interactive shells are not supported by this transport",
+        ))
+    }
+
+    fn like(&self, _user: String) -> Result<(String, String), RemoteError> {
+        Err(RemoteError::new("like is not supported by this transport"))
+    }
+}
+
+#[derive(Clone)]
+pub enum TransportKind {
+    Ssh,
+    Local(PathBuf),
+}
+
+impl TransportKind {
+    pub fn connect(
+        &self,
+        row: u8,
+        column: u8,
+        options: &ConnectOptions,
+        observer: &dyn Observer,
+    ) -> Result<Box<dyn Transport>, RemoteError> {
+        Ok(match self {
+            TransportKind::Ssh => Box::new(OlinfoClient::connect(row, column, options, observer)?),
+            TransportKind::Local(root) => Box::new(LocalHost::new(root, row, column)?),
+        })
+    }
+}
+
+impl Transport for OlinfoClient {
+    fn exec(&self, command: &str, options: &RunOptions) -> Result<Output, RemoteError> {
+        self.run(command, options)
+    }
+
+    fn upload(&self, filename: &str, observer: &dyn Observer) -> Result<(), RemoteError> {
+        self.send(filename.to_string(), observer)
+    }
+
+    fn download(&self, filename: &str, observer: &dyn Observer) -> Result<(), RemoteError> {
+        self.recv(filename.to_string(), observer)
+    }
+
+    fn set_keepalive(&self, interval: u32) {
+        OlinfoClient::set_keepalive(self, interval)
+    }
+
+    fn keepalive(&self) -> Result<(), RemoteError> {
+        self.keepalive_send().map(|_| ())
+    }
+
+    fn shell(
+        &self,
+        size: (u16, u16),
+        input: Receiver<ShellInput>,
+        output: Sender<Vec<u8>>,
+    ) -> Result<i32, RemoteError> {
+        OlinfoClient::shell(self, size, input, output)
+    }
+
+    #[cfg(feature = "like")]
+    fn like(&self, user: String) -> Result<(String, String), RemoteError> {
+        OlinfoClient::like(self, user)
+    }
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "async")]
+
+use core::engine::{Engine, Task};
+use core::RunOptions;
+use testing::Sshd;
+
+#[test]
+#[ignore = "needs sshd"]
+fn engine_runs_on_every_target() {
+    let servers: Vec<Sshd> = (0..3).map(|_| Sshd::start()).collect();
+    let targets = servers
+        .iter()
+        .enumerate()
+        .map(|(i, sshd)| {
+            let task = Task::Run("echo $((6 * 7))".to_string(), RunOptions::default());
+            (1, i as u8 + 1, sshd.options(), task)
+        })
+        .collect();
+    let results = Engine::new(2, 2).perform(targets);
+    assert_eq!(results.len(), 3);
+    for (i, (row, column, result)) in results.into_iter().enumerate() {
+        assert_eq!((row, column), (1, i as u8 + 1));
+        let output = result.unwrap().unwrap();
+        assert_eq!(output.stdout, "42\n");
+        assert_eq!(output.status, 0);
+    }
+}
@@ -0,0 +1,84 @@
+use core::{recv_destination, Become, BecomeMethod, LocalHost, Observer, RunOptions, Transport};
+use std::cell::Cell;
+use std::fs;
+use std::sync::Arc;
+
+struct Counter(Cell<u64>);
+
+impl Observer for Counter {
+    fn transferred(&self, bytes: u64) {
+        self.0.set(self.0.get() + bytes);
+    }
+}
+
+fn sandbox() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("remote-local")
+        .tempdir()
+        .unwrap()
+}
+
+#[test]
+fn exec_runs_inside_the_sandbox() {
+    let root = sandbox();
+    let host = LocalHost::new(root.path(), 2, 3).unwrap();
+    let options = RunOptions {
+        env: vec![("SEAT".to_string(), "B3".to_string())],
+        input: Some(Arc::new(b"input".to_vec())),
+        ..Default::default()
+    };
+    let output = host
+        .exec(
+            "echo $SEAT > seat; pwd; cat; echo err >&2; exit 4",
+            &options,
+        )
+        .unwrap();
+    assert_eq!(
+        output.stdout,
+        format!("{}\ninput", root.path().join("B3").display())
+    );
+    assert_eq!(output.stderr, "err\n");
+    assert_eq!(output.status, 4);
+    assert_eq!(
+        fs::read_to_string(root.path().join("B3/seat")).unwrap(),
+        "B3\n"
+    );
+}
+
+#[test]
+fn upload_and_download_use_the_sandbox() {
+    let root = sandbox();
+    let host = LocalHost::new(root.path(), 1, 2).unwrap();
+    let file = root.path().join("upload.txt");
+    fs::write(&file, "payload").unwrap();
+    let counter = Counter(Cell::new(0));
+    host.upload(&file.display().to_string(), &counter).unwrap();
+    assert_eq!(counter.0.get(), 7);
+    assert_eq!(
+        fs::read_to_string(root.path().join("A2/upload.txt")).unwrap(),
+        "payload"
+    );
+
+    let name = format!("download-{}.txt", std::process::id());
+    fs::write(root.path().join("A2").join(&name), "back").unwrap();
+    host.download(&name, &counter).unwrap();
+    assert_eq!(counter.0.get(), 11);
+    let received = recv_destination(&name, 1, 2);
+    assert_eq!(fs::read_to_string(&received).unwrap(), "back");
+    fs::remove_file(&received).unwrap();
+}
+
+#[test]
+fn escalation_is_refused() {
+    let root = sandbox();
+    let host = LocalHost::new(root.path(), 1, 1).unwrap();
+    let options = RunOptions {
+        escalation: Some(Become {
+            method: BecomeMethod::Sudo,
+            user: "root".to_string(),
+            password: None,
+        }),
+        ..Default::default()
+    };
+    assert!(host.exec("true", &options).is_err());
+}
@@ -0,0 +1,119 @@
+use core::{recv_destination, OlinfoClient, RunOptions, Timeouts};
+use std::fs;
+use std::net::TcpListener;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use testing::{keygen, Sshd};
+
+fn connect(sshd: &Sshd) -> OlinfoClient {
+    OlinfoClient::connect(1, 1, &sshd.options(), &()).unwrap()
+}
+
+#[test]
+#[ignore = "needs sshd"]
+fn run_captures_output_and_status() {
+    let sshd = Sshd::start();
+    let output = connect(&sshd)
+        .run("echo out; echo err >&2; exit 3", &RunOptions::default())
+        .unwrap();
+    assert_eq!(output.stdout, "out\n");
+    assert_eq!(output.stderr, "err\n");
+    assert_eq!(output.status, 3);
+}
+
+#[test]
+#[ignore = "needs sshd"]
+fn run_applies_options() {
+    let sshd = Sshd::start();
+    let options = RunOptions {
+        env: vec![("GREETING".to_string(), "hello world".to_string())],
+        cwd: Some(sshd.dir.path().display().to_string()),
+        input: Some(Arc::new(b"from stdin".to_vec())),
+        ..Default::default()
+    };
+    let output = connect(&sshd)
+        .run("echo \"$GREETING\"; pwd; cat", &options)
+        .unwrap();
+    assert_eq!(
+        output.stdout,
+        format!("hello world\n{}\nfrom stdin", sshd.dir.path().display())
+    );
+    assert_eq!(output.status, 0);
+}
+
+#[test]
+#[ignore = "needs sshd"]
+fn send_and_recv_round_trip() {
+    let sshd = Sshd::start();
+    let client = connect(&sshd);
+    let name = format!("remote-test-{}-{}.txt", process::id(), sshd.port);
+    let local = sshd.path(&name);
+    fs::write(&local, "payload\n").unwrap();
+    client.send(local.display().to_string(), &()).unwrap();
+    let output = client
+        .run(
+            &format!("cat {0} && mv {0} {0}.remote", name),
+            &RunOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(output.stdout, "payload\n");
+
+    let remote = format!("{}.remote", name);
+    client.recv(remote.clone(), &()).unwrap();
+    let received = recv_destination(&remote, 1, 1);
+    assert_eq!(fs::read_to_string(&received).unwrap(), "payload\n");
+    fs::remove_file(&received).unwrap();
+    client
+        .run(&format!("rm {}", remote), &RunOptions::default())
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs sshd"]
+fn unauthorized_key_is_rejected() {
+    let sshd = Sshd::start();
+    keygen(&sshd.path("other_key"));
+    let mut options = sshd.options();
+    options.identity_file = Some(sshd.path("other_key"));
+    assert!(OlinfoClient::connect(1, 1, &options, &()).is_err());
+}
+
+#[test]
+#[ignore = "needs sshd"]
+fn host_key_mismatch_is_rejected() {
+    let sshd = Sshd::start();
+    keygen(&sshd.path("other_host_key"));
+    let key = fs::read_to_string(sshd.path("other_host_key.pub")).unwrap();
+    let mut options = sshd.options();
+    let known_hosts = sshd.path("wrong_known_hosts");
+    fs::write(&known_hosts, format!("[127.0.0.1]:{} {}", sshd.port, key)).unwrap();
+    options.known_hosts = Some(known_hosts);
+    let error = OlinfoClient::connect(1, 1, &options, &()).err().unwrap();
+    assert!(error.to_string().contains("mismatch"), "{}", error);
+}
+
+#[test]
+fn silent_server_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        thread::sleep(Duration::from_secs(3));
+        drop(stream);
+    });
+    let options = core::ConnectOptions {
+        timeouts: Timeouts {
+            connect: Duration::from_secs(1),
+            handshake: Duration::from_secs(1),
+        },
+        host: Some("127.0.0.1".to_string()),
+        port: Some(port),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    assert!(OlinfoClient::connect(1, 1, &options, &()).is_err());
+    assert!(start.elapsed() < Duration::from_secs(3));
+    server.join().unwrap();
+}
@@ -1,9 +1,17 @@
+#[cfg(feature = "python")]
 use cpython;
+#[cfg(feature = "cookies")]
 use crypto::symmetriccipher;
+use regex;
+use serde_json;
+#[cfg(feature = "cookies")]
 use sqlite;
+#[cfg(feature = "ssh")]
 use ssh2;
 use std::{fmt, io, string};
+use toml;
 
+#[derive(Debug)]
 pub struct RemoteError {
     message: String,
 }
@@ -44,11 +52,20 @@ macro_rules! add_remote_error {
 }
 
 add_remote_error!(io::Error);
-add_remote_error!(sqlite::Error);
-add_remote_error!(ssh2::Error);
+add_remote_error!(regex::Error);
+add_remote_error!(serde_json::Error);
 add_remote_error!(string::FromUtf8Error);
+add_remote_error!(toml::de::Error);
+
+#[cfg(feature = "cookies")]
+add_remote_error!(sqlite::Error);
+#[cfg(feature = "cookies")]
 add_remote_error!(symmetriccipher::SymmetricCipherError, "{:?}");
 
+#[cfg(feature = "ssh")]
+add_remote_error!(ssh2::Error);
+
+#[cfg(feature = "python")]
 impl From<cpython::PyErr> for RemoteError {
     fn from(error: cpython::PyErr) -> RemoteError {
         RemoteError {
@@ -0,0 +1,80 @@
+use core::{RunOptions, Transport};
+use error::RemoteError;
+use serde::Serialize;
+use std::str::FromStr;
+
+const PROBE_SH: &'static str = include_str!("./probe.sh");
+
+#[derive(Serialize, Default, Clone)]
+pub struct Facts {
+    pub hostname: String,
+    pub os: String,
+    pub kernel: String,
+    pub uptime: u64,
+    pub cpu: String,
+    pub cpus: u32,
+    pub memory: u64,
+    pub disk_total: u64,
+    pub disk_used: u64,
+    pub users: Vec<String>,
+    pub addresses: Vec<String>,
+}
+
+fn words(value: &str) -> Vec<String> {
+    value.split_whitespace().map(|w| w.to_string()).collect()
+}
+
+fn invalid(key: &str, value: &str) -> RemoteError {
+    RemoteError::new(&format!("invalid {} in probe output: {:?}", key, value))
+}
+
+fn number<T: FromStr>(key: &str, value: &str) -> Result<T, RemoteError> {
+    value.parse().map_err(|_| invalid(key, value))
+}
+
+pub fn parse(output: &str) -> Result<Facts, RemoteError> {
+    let mut facts = Facts::default();
+    for line in output.lines() {
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap();
+        let value = match parts.next() {
+            Some(value) => value.trim(),
+            None => continue,
+        };
+        match key {
+            "hostname" => facts.hostname = value.to_string(),
+            "os" => facts.os = value.to_string(),
+            "kernel" => facts.kernel = value.to_string(),
+            "uptime" => facts.uptime = number(key, value)?,
+            "cpu" => facts.cpu = value.to_string(),
+            "cpus" => facts.cpus = number(key, value)?,
+            "memory" => facts.memory = number::<u64>(key, value)? * 1024,
+            "disk" => match value.split_whitespace().collect::<Vec<&str>>()[..] {
+                [total, used] => {
+                    facts.disk_total = number::<u64>(key, total)? * 1024;
+                    facts.disk_used = number::<u64>(key, used)? * 1024;
+                }
+                _ => return Err(invalid(key, value)),
+            },
+            "users" => facts.users = words(value),
+            "addresses" => facts.addresses = words(value),
+            _ => {}
+        }
+    }
+    if facts.hostname.is_empty() {
+        return Err(RemoteError::new("unexpected probe output"));
+    }
+    Ok(facts)
+}
+
+pub fn gather(client: &dyn Transport) -> Result<Facts, RemoteError> {
+    let output = client.exec(PROBE_SH, &RunOptions::default())?;
+    if output.status != 0 {
+        return Err(RemoteError::new(&format!(
+            "probe exited with status {}: {}",
+            output.status,
+            output.stderr.trim()
+        )));
+    }
+    parse(&output.stdout)
+}
@@ -0,0 +1,11 @@
+echo "hostname=$(hostname)"
+. /etc/os-release 2>/dev/null
+echo "os=$PRETTY_NAME"
+echo "kernel=$(uname -r)"
+echo "uptime=$(cut -d. -f1 /proc/uptime)"
+echo "cpu=$(grep -m1 'model name' /proc/cpuinfo | cut -d: -f2- | sed 's/^ *//')"
+echo "cpus=$(nproc)"
+echo "memory=$(awk '/^MemTotal:/ {print $2}' /proc/meminfo)"
+echo "disk=$(df -Pk / | awk 'NR == 2 {print $2, $3}')"
+echo "users=$(who | awk '{print $1}' | sort -u | tr '\n' ' ')"
+echo "addresses=$(hostname -I 2>/dev/null)"
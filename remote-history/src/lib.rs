@@ -0,0 +1,142 @@
+use error::RemoteError;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HostResult {
+    pub host: String,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub id: u64,
+    pub timestamp: String,
+    pub user: String,
+    pub target: String,
+    pub hosts: Vec<String>,
+    pub subcommand: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub results: Vec<HostResult>,
+}
+
+impl Entry {
+    pub fn new(target: &str, hosts: Vec<String>, subcommand: &str, args: Vec<String>) -> Entry {
+        let command = match args.iter().position(|a| a == subcommand) {
+            Some(i) => args[i + 1..].join(" "),
+            None => String::new(),
+        };
+        Entry {
+            id: 0,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            user: env::var("USER").unwrap_or_default(),
+            target: target.to_string(),
+            hosts: hosts,
+            subcommand: subcommand.to_string(),
+            command: command,
+            args: args,
+            results: Vec::new(),
+        }
+    }
+
+    pub fn hosts_with(&self, outcome: Outcome) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|r| r.outcome == outcome)
+            .map(|r| r.host.clone())
+            .collect()
+    }
+
+    pub fn hosts_nonzero(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|r| r.status.is_some_and(|s| s != 0))
+            .map(|r| r.host.clone())
+            .collect()
+    }
+}
+
+pub fn path() -> PathBuf {
+    let mut path = match env::var_os("XDG_DATA_HOME") {
+        Some(data) => PathBuf::from(data),
+        None => {
+            let mut home = PathBuf::from(env::var_os("HOME").unwrap_or_default());
+            home.push(".local/share");
+            home
+        }
+    };
+    path.push("remote/history.jsonl");
+    path
+}
+
+pub fn load() -> Result<Vec<Entry>, RemoteError> {
+    let file = match File::open(path()) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            entries.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(entries)
+}
+
+pub fn find(id: u64) -> Result<Entry, RemoteError> {
+    load()?
+        .into_iter()
+        .find(|e| e.id == id)
+        .ok_or_else(|| RemoteError::new(&format!("no run with id {}", id)))
+}
+
+pub fn last() -> Result<Option<Entry>, RemoteError> {
+    Ok(load()?.pop())
+}
+
+pub fn append(entry: &mut Entry) -> Result<u64, RemoteError> {
+    let path = path();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    file.lock_exclusive()?;
+    file.seek(SeekFrom::Start(0))?;
+    let mut last = None;
+    for line in BufReader::new(&file).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    entry.id = match last {
+        Some(line) => serde_json::from_str::<Entry>(&line)?.id + 1,
+        None => 1,
+    };
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    file.unlock()?;
+    Ok(entry.id)
+}
@@ -0,0 +1,193 @@
+use core::{Observer, RunOptions, Transport};
+use error::RemoteError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum When {
+    Always,
+    #[default]
+    Success,
+    Failure,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStep {
+    name: Option<String>,
+    run: Option<String>,
+    send: Option<String>,
+    recv: Option<String>,
+    wait: Option<u64>,
+    #[serde(default)]
+    when: When,
+    exit: Option<i32>,
+    #[serde(default)]
+    ignore_errors: bool,
+    cwd: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPlaybook {
+    #[serde(default)]
+    step: Vec<RawStep>,
+}
+
+pub enum Task {
+    Run(String),
+    Send(String),
+    Recv(String),
+    Wait(u64),
+}
+
+pub struct Step {
+    pub name: String,
+    pub task: Task,
+    pub when: When,
+    pub exit: Option<i32>,
+    pub ignore_errors: bool,
+    pub cwd: Option<String>,
+    pub env: Vec<(String, String)>,
+}
+
+pub struct Playbook {
+    pub steps: Vec<Step>,
+}
+
+impl Step {
+    fn from_raw(index: usize, raw: RawStep) -> Result<Step, RemoteError> {
+        let mut tasks = Vec::new();
+        if let Some(command) = raw.run {
+            tasks.push(Task::Run(command));
+        }
+        if let Some(file) = raw.send {
+            tasks.push(Task::Send(file));
+        }
+        if let Some(file) = raw.recv {
+            tasks.push(Task::Recv(file));
+        }
+        if let Some(seconds) = raw.wait {
+            tasks.push(Task::Wait(seconds));
+        }
+        if tasks.len() != 1 {
+            return Err(RemoteError::new(&format!(
+                "step {} must have exactly one of run, send, recv or wait",
+                index + 1
+            )));
+        }
+        let task = tasks.pop().unwrap();
+        Ok(Step {
+            name: raw.name.unwrap_or_else(|| match &task {
+                Task::Run(command) => format!("run {}", command),
+                Task::Send(file) => format!("send {}", file),
+                Task::Recv(file) => format!("recv {}", file),
+                Task::Wait(seconds) => format!("wait {}s", seconds),
+            }),
+            task: task,
+            when: raw.when,
+            exit: raw.exit,
+            ignore_errors: raw.ignore_errors,
+            cwd: raw.cwd,
+            env: raw.env.into_iter().collect(),
+        })
+    }
+
+    fn should_run(&self, previous: Option<i32>) -> bool {
+        let previous = previous.unwrap_or(0);
+        if let Some(exit) = self.exit {
+            return previous == exit;
+        }
+        match self.when {
+            When::Always => true,
+            When::Success => previous == 0,
+            When::Failure => previous != 0,
+        }
+    }
+
+    pub fn options(&self, base: &RunOptions) -> RunOptions {
+        let mut options = base.clone();
+        options.env.extend(self.env.iter().cloned());
+        if self.cwd.is_some() {
+            options.cwd = self.cwd.clone();
+        }
+        options
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        base: &RunOptions,
+        observer: &dyn Observer,
+    ) -> Result<i32, RemoteError> {
+        match &self.task {
+            Task::Run(command) => Ok(client.exec(command, &self.options(base))?.status),
+            Task::Send(file) => client.upload(file, observer).map(|_| 0),
+            Task::Recv(file) => client.download(file, observer).map(|_| 0),
+            Task::Wait(seconds) => {
+                thread::sleep(Duration::from_secs(*seconds));
+                Ok(0)
+            }
+        }
+    }
+}
+
+impl Playbook {
+    pub fn load(path: &Path) -> Result<Playbook, RemoteError> {
+        let raw: RawPlaybook = toml::from_str(&read_to_string(path)?)?;
+        let mut steps = Vec::new();
+        for (i, step) in raw.step.into_iter().enumerate() {
+            steps.push(Step::from_raw(i, step)?);
+        }
+        Ok(Playbook { steps: steps })
+    }
+
+    pub fn play<F>(
+        &self,
+        client: &dyn Transport,
+        options: &RunOptions,
+        observer: &dyn Observer,
+        progress: F,
+    ) -> Result<String, RemoteError>
+    where
+        F: Fn(usize, &Step),
+    {
+        let mut report = Vec::new();
+        let mut previous = None;
+        for (i, step) in self.steps.iter().enumerate() {
+            if !step.should_run(previous) {
+                report.push(format!("[skipped] {}", step.name));
+                continue;
+            }
+            progress(i, step);
+            let (status, failure) = match step.execute(client, options, observer) {
+                Ok(0) => (0, None),
+                Ok(status) => (status, Some(format!("exit status {}", status))),
+                Err(e) => (-1, Some(e.to_string())),
+            };
+            previous = Some(status);
+            match failure {
+                None => report.push(format!("[ok] {}", step.name)),
+                Some(reason) if step.ignore_errors => {
+                    report.push(format!("[failed] {}: {}", step.name, reason))
+                }
+                Some(reason) => {
+                    return Err(RemoteError::new(&format!(
+                        "stopped at step {} ({}): {}",
+                        i + 1,
+                        step.name,
+                        reason
+                    )))
+                }
+            }
+        }
+        Ok(report.join("\n"))
+    }
+}
@@ -0,0 +1,156 @@
+use core::{ConnectOptions, Timeouts};
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const SSHD_PATHS: [&str; 3] = ["/usr/sbin/sshd", "/usr/local/sbin/sshd", "/usr/bin/sshd"];
+
+fn which(name: &str, fallbacks: &[&str]) -> Option<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .chain(fallbacks.iter().map(PathBuf::from))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn keygen(path: &Path) {
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+        .arg(path)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success(), "ssh-keygen failed");
+}
+
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+pub fn user() -> String {
+    match env::var("USER") {
+        Ok(user) => user,
+        Err(_) => {
+            let output = Command::new("id").arg("-un").output().unwrap();
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        }
+    }
+}
+
+pub struct Sshd {
+    pub dir: TempDir,
+    pub port: u16,
+    child: Child,
+}
+
+impl Sshd {
+    pub fn start() -> Sshd {
+        let sshd = match (which("sshd", &SSHD_PATHS), which("ssh-keygen", &[])) {
+            (Some(sshd), Some(_)) => sshd,
+            _ => panic!("sshd or ssh-keygen not found"),
+        };
+        let dir = tempfile::Builder::new()
+            .prefix("remote-sshd")
+            .tempdir()
+            .unwrap();
+        let path = dir.path();
+        keygen(&path.join("host_key"));
+        keygen(&path.join("user_key"));
+        fs::copy(path.join("user_key.pub"), path.join("authorized_keys")).unwrap();
+        let port = free_port();
+        let mut config = File::create(path.join("sshd_config")).unwrap();
+        writeln!(
+            config,
+            "Port {port}\n\
+             ListenAddress 127.0.0.1\n\
+             HostKey {dir}/host_key\n\
+             PidFile {dir}/sshd.pid\n\
+             AuthorizedKeysFile {dir}/authorized_keys\n\
+             PasswordAuthentication no\n\
+             UsePAM no\n\
+             StrictModes no\n\
+             Subsystem sftp internal-sftp",
+            port = port,
+            dir = path.display()
+        )
+        .unwrap();
+        let child = Command::new(sshd)
+            .arg("-D")
+            .arg("-e")
+            .arg("-f")
+            .arg(path.join("sshd_config"))
+            .stderr(File::create(path.join("sshd.log")).unwrap())
+            .spawn()
+            .unwrap();
+        let sshd = Sshd {
+            dir: dir,
+            port: port,
+            child: child,
+        };
+        let start = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            if start.elapsed() > Duration::from_secs(10) {
+                panic!(
+                    "sshd did not start: {}",
+                    fs::read_to_string(sshd.path("sshd.log")).unwrap_or_default()
+                );
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        sshd
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn known_hosts(&self) -> PathBuf {
+        let key = fs::read_to_string(self.path("host_key.pub")).unwrap();
+        let path = self.path("known_hosts");
+        fs::write(&path, format!("[127.0.0.1]:{} {}", self.port, key)).unwrap();
+        path
+    }
+
+    pub fn options(&self) -> ConnectOptions {
+        ConnectOptions {
+            timeouts: Timeouts {
+                connect: Duration::from_secs(5),
+                handshake: Duration::from_secs(5),
+            },
+            host: Some("127.0.0.1".to_string()),
+            port: Some(self.port),
+            user: Some(user()),
+            identity_file: Some(self.path("user_key")),
+            known_hosts: Some(self.known_hosts()),
+            ..Default::default()
+        }
+    }
+
+    pub fn ssh_config(&self, seat: &str) -> String {
+        format!(
+            "Host {}\n    HostName 127.0.0.1\n    Port {}\n    User {}\n    IdentityFile {}\n    UserKnownHostsFile {}\n",
+            seat,
+            self.port,
+            user(),
+            self.path("user_key").display(),
+            self.known_hosts().display()
+        )
+    }
+}
+
+impl Drop for Sshd {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
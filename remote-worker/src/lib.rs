@@ -1,52 +1,153 @@
+use core::Observer;
 use error::RemoteError;
-use std::cell::Cell;
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum WorkerStatus {
     Running,
     Resolved,
     Rejected,
 }
 
-pub struct Worker {
-    process: thread::JoinHandle<Result<(String, String), RemoteError>>,
-    receiver: mpsc::Receiver<WorkerStatus>,
-    status: Cell<WorkerStatus>,
+#[derive(Copy, Clone, PartialEq)]
+pub enum Phase {
+    Connecting,
+    Authenticating,
+    Running,
+    Transferring,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Connecting => "connecting",
+            Phase::Authenticating => "authenticating",
+            Phase::Running => "running",
+            Phase::Transferring => "transferring",
+        }
+    }
 }
 
-impl Worker {
-    pub fn new<F>(f: F) -> Worker
+pub enum Event {
+    Phase(Phase),
+    Bytes(u64),
+    Detail(String),
+    Finished(WorkerStatus, Instant),
+}
+
+#[derive(Clone)]
+pub struct Progress {
+    id: usize,
+    sender: mpsc::Sender<(usize, Event)>,
+}
+
+impl Progress {
+    fn send(&self, event: Event) {
+        let _ = self.sender.send((self.id, event));
+    }
+
+    pub fn phase(&self, phase: Phase) {
+        self.send(Event::Phase(phase));
+    }
+
+    pub fn bytes(&self, bytes: u64) {
+        self.send(Event::Bytes(bytes));
+    }
+
+    pub fn detail(&self, detail: String) {
+        self.send(Event::Detail(detail));
+    }
+}
+
+impl Observer for Progress {
+    fn authenticating(&self) {
+        self.phase(Phase::Authenticating);
+    }
+
+    fn transferred(&self, bytes: u64) {
+        self.bytes(bytes);
+    }
+}
+
+pub struct State {
+    pub status: WorkerStatus,
+    pub phase: Phase,
+    pub bytes: u64,
+    pub detail: String,
+    pub started: Instant,
+    pub finished: Option<Instant>,
+}
+
+impl State {
+    pub fn new(started: Instant) -> State {
+        State {
+            status: WorkerStatus::Running,
+            phase: Phase::Connecting,
+            bytes: 0,
+            detail: String::new(),
+            started: started,
+            finished: None,
+        }
+    }
+
+    pub fn apply(&mut self, event: Event) {
+        match event {
+            Event::Phase(phase) => self.phase = phase,
+            Event::Bytes(bytes) => self.bytes += bytes,
+            Event::Detail(detail) => self.detail = detail,
+            Event::Finished(status, at) => {
+                self.status = status;
+                self.finished = Some(at);
+            }
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.finished {
+            Some(finished) => finished - self.started,
+            None => self.started.elapsed(),
+        }
+    }
+}
+
+pub struct Worker<T> {
+    process: thread::JoinHandle<Result<T, RemoteError>>,
+    started: Instant,
+}
+
+impl<T: Send + 'static> Worker<T> {
+    pub fn new<F>(id: usize, events: &mpsc::Sender<(usize, Event)>, f: F) -> Worker<T>
     where
-        F: FnOnce() -> Result<(String, String), RemoteError>,
+        F: FnOnce(&Progress) -> Result<T, RemoteError>,
         F: Send + 'static,
     {
-        let (send, recv) = mpsc::channel();
+        let progress = Progress {
+            id: id,
+            sender: events.clone(),
+        };
         Worker {
             process: thread::spawn(move || {
-                let result = f();
-                match result {
-                    Ok(_) => send.send(WorkerStatus::Resolved).unwrap(),
-                    Err(_) => send.send(WorkerStatus::Rejected).unwrap(),
+                let result = f(&progress);
+                let status = match result {
+                    Ok(_) => WorkerStatus::Resolved,
+                    Err(_) => WorkerStatus::Rejected,
                 };
+                progress.send(Event::Finished(status, Instant::now()));
                 result
             }),
-            receiver: recv,
-            status: Cell::new(WorkerStatus::Running),
+            started: Instant::now(),
         }
     }
+}
 
-    pub fn get_status(&self) -> WorkerStatus {
-        if let WorkerStatus::Running = self.status.get() {
-            if let Ok(s) = self.receiver.try_recv() {
-                self.status.set(s);
-            }
-        }
-        self.status.get()
+impl<T> Worker<T> {
+    pub fn started(&self) -> Instant {
+        self.started
     }
 
-    pub fn join(self) -> Result<(String, String), RemoteError> {
+    pub fn join(self) -> Result<T, RemoteError> {
         self.process.join().unwrap()
     }
 }
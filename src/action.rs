@@ -0,0 +1,938 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use config::Config;
+#[cfg(feature = "async")]
+use core::engine;
+use core::{
+    escape, host, probe, recv_destination, send_destination, ConnectOptions, Output, Probe,
+    RunOptions, SessionPool, Transport,
+};
+use error::RemoteError;
+use facts::Facts;
+use playbook::{Playbook, Task};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+use worker::{Phase, Progress};
+
+pub enum Report {
+    Command(Output),
+    Transfer(u64),
+    Text(String, String),
+    Probe(Probe),
+    Facts(Facts),
+}
+
+impl Report {
+    pub fn status(&self) -> Option<i32> {
+        match self {
+            Report::Command(output) => Some(output.status),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&RemoteError> {
+        match self {
+            Report::Probe(probe) => probe.error.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn text(self) -> (String, String) {
+        match self {
+            Report::Command(output) => (output.stdout, output.stderr),
+            Report::Transfer(bytes) => (
+                format!("{} transferred", crate::human_size(bytes)),
+                String::new(),
+            ),
+            Report::Text(stdout, stderr) => (stdout, stderr),
+            Report::Probe(probe) => (
+                probe.banner.unwrap_or_default(),
+                probe.error.map(|e| e.to_string()).unwrap_or_default(),
+            ),
+            Report::Facts(facts) => (facts.hostname, String::new()),
+        }
+    }
+}
+
+pub struct Target {
+    pub row: u8,
+    pub column: u8,
+    pub vars: BTreeMap<String, String>,
+    pub options: ConnectOptions,
+}
+
+impl Target {
+    pub fn new(
+        row: u8,
+        column: u8,
+        options: ConnectOptions,
+        inventory: BTreeMap<String, String>,
+    ) -> Target {
+        let mut vars = inventory;
+        vars.insert("host".to_string(), crate::host_name(row, column));
+        vars.insert("row".to_string(), ((64 + row) as char).to_string());
+        vars.insert("col".to_string(), column.to_string());
+        vars.insert(
+            "addr".to_string(),
+            options.host.clone().unwrap_or_else(|| host(row, column)),
+        );
+        Target {
+            row: row,
+            column: column,
+            vars: vars,
+            options: options,
+        }
+    }
+
+    pub fn command(&self, template: &str, params: &BTreeMap<String, String>) -> String {
+        escape::substitute(template, |name| {
+            params
+                .get(name)
+                .or_else(|| self.vars.get(name))
+                .map(|v| escape::escape(v))
+        })
+    }
+
+    pub fn path(&self, template: &str) -> String {
+        escape::substitute(template, |name| self.vars.get(name).cloned())
+    }
+}
+
+pub trait Action {
+    fn name(&self) -> &str;
+
+    fn subcommand(&self) -> App<'_, '_>;
+
+    fn targets(&self) -> Option<&str> {
+        None
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError>;
+}
+
+pub trait Operation: Send + Sync {
+    fn describe(&self, target: &Target) -> Vec<String>;
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        progress: &Progress,
+        target: &Target,
+    ) -> Result<Report, RemoteError>;
+
+    fn perform(
+        &self,
+        pool: &SessionPool,
+        progress: &Progress,
+        target: &Target,
+    ) -> Result<Report, RemoteError> {
+        pool.with(target.row, target.column, progress, |client| {
+            progress.phase(Phase::Running);
+            self.execute(client, progress, target)
+        })
+    }
+
+    fn render(&self, report: Report) -> (String, String) {
+        report.text()
+    }
+
+    fn tabulate(&self, _results: &[(u8, u8, &Result<Report, RemoteError>)]) -> Option<String> {
+        None
+    }
+
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    fn supports_local(&self) -> bool {
+        true
+    }
+
+    #[cfg(feature = "async")]
+    fn task(&self, _target: &Target) -> Option<engine::Task> {
+        None
+    }
+}
+
+fn firefox() -> config::Alias {
+    config::Alias {
+        command: "firefox {url}".to_string(),
+        help: Some("Open web page remotely".to_string()),
+        args: vec!["url".to_string()],
+        env: vec![("DISPLAY".to_string(), ":0".to_string())]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    }
+}
+
+pub fn registry(config: &Config, reserved: &[&str]) -> Result<Vec<Box<dyn Action>>, RemoteError> {
+    let mut actions: Vec<Box<dyn Action>> = vec![
+        Box::new(Run),
+        Box::new(SendFile),
+        Box::new(RecvFile),
+        Box::new(Play),
+        Box::new(Ping),
+        Box::new(Gather),
+    ];
+    if cfg!(feature = "like") {
+        actions.push(Box::new(Like));
+    }
+    let builtin: Vec<String> = actions.iter().map(|a| a.name().to_string()).collect();
+    if !config.aliases.contains_key("firefox") {
+        actions.push(Box::new(Alias::new("firefox", firefox())));
+    }
+    for (name, alias) in &config.aliases {
+        if builtin.contains(name) || reserved.contains(&name.as_str()) || name == "help" {
+            return Err(RemoteError::new(&format!(
+                "alias {} shadows a built-in subcommand",
+                name
+            )));
+        }
+        if alias.command.is_empty() {
+            return Err(RemoteError::new(&format!("alias {} has no command", name)));
+        }
+        let mut params = vec!["help", "version"];
+        for param in alias.args.iter().chain(alias.options.keys()) {
+            if params.contains(&param.as_str()) {
+                return Err(RemoteError::new(&format!(
+                    "alias {} has a duplicate or reserved parameter {}",
+                    name, param
+                )));
+            }
+            params.push(param);
+        }
+        actions.push(Box::new(Alias::new(name, alias.clone())));
+    }
+    Ok(actions)
+}
+
+pub fn describe_run(command: &str, options: &RunOptions) -> Vec<String> {
+    let mut plan = vec![format!("run: {}", options.command_line(command))];
+    if let Some(input) = &options.input {
+        plan.push(format!("stdin: {} bytes", input.len()));
+    }
+    if let Some((width, height)) = options.pty {
+        plan.push(format!("pty: {}x{}", width, height));
+    }
+    plan
+}
+
+fn describe_send(file: &str) -> String {
+    format!(
+        "send: {} -> {} ({})",
+        file,
+        send_destination(file),
+        match fs::metadata(file) {
+            Ok(metadata) => format!("{} bytes", metadata.len()),
+            Err(e) => e.to_string(),
+        }
+    )
+}
+
+fn describe_recv(file: &str, row: u8, column: u8) -> String {
+    format!(
+        "recv: {} -> {} (size unknown)",
+        file,
+        recv_destination(file, row, column)
+    )
+}
+
+pub struct Command {
+    pub command: String,
+    pub params: BTreeMap<String, String>,
+    pub options: RunOptions,
+}
+
+impl Operation for Command {
+    fn describe(&self, target: &Target) -> Vec<String> {
+        describe_run(&target.command(&self.command, &self.params), &self.options)
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        _progress: &Progress,
+        target: &Target,
+    ) -> Result<Report, RemoteError> {
+        Ok(Report::Command(client.exec(
+            &target.command(&self.command, &self.params),
+            &self.options,
+        )?))
+    }
+
+    #[cfg(feature = "async")]
+    fn task(&self, target: &Target) -> Option<engine::Task> {
+        Some(engine::Task::Run(
+            target.command(&self.command, &self.params),
+            self.options.clone(),
+        ))
+    }
+}
+
+struct Run;
+
+impl Action for Run {
+    fn name(&self) -> &str {
+        "run"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("run")
+            .about("Runs command remotely")
+            .arg(
+                Arg::with_name("command")
+                    .required(true)
+                    .index(1)
+                    .help("command to run")
+                    .min_values(1),
+            )
+            .arg(
+                Arg::with_name("stdin")
+                    .long("stdin")
+                    .help("pipes local stdin to every remote command"),
+            )
+            .arg(
+                Arg::with_name("stdin-file")
+                    .long("stdin-file")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .conflicts_with("stdin")
+                    .help("pipes a local file to every remote command"),
+            )
+            .arg(
+                Arg::with_name("env")
+                    .short("e")
+                    .long("env")
+                    .value_name("KEY=VALUE")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .validator(|v| {
+                        if v.contains('=') {
+                            Ok(())
+                        } else {
+                            Err("expected KEY=VALUE".to_string())
+                        }
+                    })
+                    .help("sets an environment variable for the command"),
+            )
+            .arg(
+                Arg::with_name("cwd")
+                    .long("cwd")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .help("runs the command in a working directory"),
+            )
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        let command: String = matches
+            .values_of("command")
+            .unwrap()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let input = if let Some(file) = matches.value_of("stdin-file") {
+            Some(Arc::new(fs::read(file)?))
+        } else if matches.is_present("stdin") {
+            let mut buffer = Vec::new();
+            io::stdin().read_to_end(&mut buffer)?;
+            Some(Arc::new(buffer))
+        } else {
+            None
+        };
+        Ok(Arc::new(Command {
+            command: command,
+            params: BTreeMap::new(),
+            options: RunOptions {
+                env: matches
+                    .values_of("env")
+                    .map(|values| {
+                        values
+                            .map(|v| {
+                                let mut parts = v.splitn(2, '=');
+                                (
+                                    parts.next().unwrap().to_string(),
+                                    parts.next().unwrap().to_string(),
+                                )
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                cwd: matches.value_of("cwd").map(|d| d.to_string()),
+                input: input,
+                ..options
+            },
+        }))
+    }
+}
+
+pub struct Alias {
+    name: String,
+    about: String,
+    alias: config::Alias,
+}
+
+impl Alias {
+    pub fn new(name: &str, alias: config::Alias) -> Alias {
+        Alias {
+            name: name.to_string(),
+            about: match &alias.help {
+                Some(help) => help.clone(),
+                None => format!("Runs `{}` remotely", alias.command),
+            },
+            alias: alias,
+        }
+    }
+}
+
+impl Action for Alias {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn targets(&self) -> Option<&str> {
+        self.alias.targets.as_deref()
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        let mut app = SubCommand::with_name(&self.name).about(self.about.as_str());
+        for (i, arg) in self.alias.args.iter().enumerate() {
+            app = app.arg(Arg::with_name(arg).required(true).index(i as u64 + 1));
+        }
+        for (name, default) in &self.alias.options {
+            app = app.arg(
+                Arg::with_name(name)
+                    .long(name)
+                    .takes_value(true)
+                    .default_value(default),
+            );
+        }
+        app
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Command {
+            command: self.alias.command.clone(),
+            params: self
+                .alias
+                .args
+                .iter()
+                .chain(self.alias.options.keys())
+                .filter_map(|name| {
+                    matches
+                        .value_of(name)
+                        .map(|value| (name.clone(), value.to_string()))
+                })
+                .collect(),
+            options: RunOptions {
+                env: self
+                    .alias
+                    .env
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+                ..options
+            },
+        }))
+    }
+}
+
+struct SendFile;
+
+struct Upload {
+    file: String,
+}
+
+impl Action for SendFile {
+    fn name(&self) -> &str {
+        "send"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("send")
+            .about("Loads file remotely")
+            .arg(
+                Arg::with_name("file")
+                    .required(true)
+                    .index(1)
+                    .help("file to send"),
+            )
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        _options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Upload {
+            file: matches.value_of("file").unwrap().to_string(),
+        }))
+    }
+}
+
+impl Operation for Upload {
+    fn describe(&self, target: &Target) -> Vec<String> {
+        vec![describe_send(&target.path(&self.file))]
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        progress: &Progress,
+        target: &Target,
+    ) -> Result<Report, RemoteError> {
+        let file = target.path(&self.file);
+        progress.phase(Phase::Transferring);
+        client.upload(&file, progress)?;
+        Ok(Report::Transfer(fs::metadata(&file)?.len()))
+    }
+
+    #[cfg(feature = "async")]
+    fn task(&self, target: &Target) -> Option<engine::Task> {
+        Some(engine::Task::Send(target.path(&self.file)))
+    }
+}
+
+struct RecvFile;
+
+struct Download {
+    file: String,
+}
+
+impl Action for RecvFile {
+    fn name(&self) -> &str {
+        "recv"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("recv")
+            .about("Downloads file remotely")
+            .arg(
+                Arg::with_name("file")
+                    .required(true)
+                    .index(1)
+                    .help("file to receive"),
+            )
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        _options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Download {
+            file: matches.value_of("file").unwrap().to_string(),
+        }))
+    }
+}
+
+impl Operation for Download {
+    fn describe(&self, target: &Target) -> Vec<String> {
+        vec![describe_recv(
+            &target.path(&self.file),
+            target.row,
+            target.column,
+        )]
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        progress: &Progress,
+        target: &Target,
+    ) -> Result<Report, RemoteError> {
+        let file = target.path(&self.file);
+        progress.phase(Phase::Transferring);
+        client.download(&file, progress)?;
+        Ok(Report::Transfer(
+            fs::metadata(recv_destination(&file, target.row, target.column))?.len(),
+        ))
+    }
+
+    #[cfg(feature = "async")]
+    fn task(&self, target: &Target) -> Option<engine::Task> {
+        Some(engine::Task::Recv(target.path(&self.file)))
+    }
+}
+
+struct Play;
+
+struct Steps {
+    playbook: Playbook,
+    options: RunOptions,
+}
+
+impl Action for Play {
+    fn name(&self) -> &str {
+        "play"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("play")
+            .about("Runs a task file remotely")
+            .arg(
+                Arg::with_name("file")
+                    .required(true)
+                    .index(1)
+                    .help("task file to play"),
+            )
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Steps {
+            playbook: Playbook::load(Path::new(matches.value_of("file").unwrap()))?,
+            options: options,
+        }))
+    }
+}
+
+impl Operation for Steps {
+    fn describe(&self, target: &Target) -> Vec<String> {
+        let mut plan = Vec::new();
+        for (i, step) in self.playbook.steps.iter().enumerate() {
+            plan.push(format!("step {}: {}", i + 1, step.name));
+            match &step.task {
+                Task::Run(command) => {
+                    for line in describe_run(command, &step.options(&self.options)) {
+                        plan.push(format!("    {}", line));
+                    }
+                }
+                Task::Send(file) => plan.push(format!("    {}", describe_send(file))),
+                Task::Recv(file) => plan.push(format!(
+                    "    {}",
+                    describe_recv(file, target.row, target.column)
+                )),
+                Task::Wait(seconds) => plan.push(format!("    wait: {} seconds", seconds)),
+            }
+        }
+        plan
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        progress: &Progress,
+        _target: &Target,
+    ) -> Result<Report, RemoteError> {
+        let report = self
+            .playbook
+            .play(client, &self.options, progress, |i, step| {
+                progress.detail(format!(
+                    "step {}/{} ({})",
+                    i + 1,
+                    self.playbook.steps.len(),
+                    step.name
+                ));
+            })?;
+        Ok(Report::Text(report, String::new()))
+    }
+}
+
+struct Like;
+
+struct Likes {
+    user: String,
+}
+
+impl Action for Like {
+    fn name(&self) -> &str {
+        "like"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("like")
+            .about("Put likes on forum.olinfo.it")
+            .arg(
+                Arg::with_name("user")
+                    .required(true)
+                    .index(1)
+                    .help("user to put likes"),
+            )
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        _options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Likes {
+            user: matches.value_of("user").unwrap().to_string(),
+        }))
+    }
+}
+
+impl Operation for Likes {
+    fn describe(&self, _target: &Target) -> Vec<String> {
+        vec![format!("like: {}", self.user)]
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        _progress: &Progress,
+        _target: &Target,
+    ) -> Result<Report, RemoteError> {
+        let (stdout, stderr) = client.like(self.user.clone())?;
+        Ok(Report::Text(stdout, stderr))
+    }
+}
+
+struct Ping;
+
+struct Probes;
+
+impl Action for Ping {
+    fn name(&self) -> &str {
+        "ping"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("ping")
+            .about("Checks reachability and authentication without running anything")
+    }
+
+    fn prepare(
+        &self,
+        _matches: &ArgMatches,
+        _options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Probes))
+    }
+}
+
+impl Operation for Probes {
+    fn describe(&self, _target: &Target) -> Vec<String> {
+        vec!["ping".to_string()]
+    }
+
+    fn execute(
+        &self,
+        _client: &dyn Transport,
+        _progress: &Progress,
+        _target: &Target,
+    ) -> Result<Report, RemoteError> {
+        unreachable!()
+    }
+
+    fn perform(
+        &self,
+        _pool: &SessionPool,
+        progress: &Progress,
+        target: &Target,
+    ) -> Result<Report, RemoteError> {
+        progress.phase(Phase::Running);
+        Ok(Report::Probe(probe(
+            target.row,
+            target.column,
+            &target.options,
+        )))
+    }
+
+    fn tabulate(&self, results: &[(u8, u8, &Result<Report, RemoteError>)]) -> Option<String> {
+        let mut table = vec![format!(
+            "{:<4} {:<20} {:>9}  {:<6} {:<28} {}",
+            "HOST", "ADDRESS", "LATENCY", "AUTH", "BANNER", "FINGERPRINT"
+        )];
+        for (row, column, result) in results {
+            let probe = match result {
+                Ok(Report::Probe(probe)) => probe,
+                _ => continue,
+            };
+            table.push(format!(
+                "{}{:<4} {:<20} {:>9}  {:<6} {:<28} {}{}",
+                crossterm::SetFg(match (&probe.latency, probe.authenticated) {
+                    (_, true) => crossterm::Color::Green,
+                    (Some(_), false) => crossterm::Color::DarkYellow,
+                    (None, false) => crossterm::Color::Red,
+                }),
+                crate::host_name(*row, *column),
+                probe.address,
+                match probe.latency {
+                    Some(latency) => format!("{} ms", latency.as_millis()),
+                    None => "-".to_string(),
+                },
+                if probe.authenticated { "ok" } else { "failed" },
+                probe.banner.as_ref().map_or("-", |b| b.as_str()),
+                probe.fingerprint.as_ref().map_or("-", |f| f.as_str()),
+                crossterm::SetFg(crossterm::Color::Reset),
+            ));
+            if let Some(e) = &probe.error {
+                table.push(format!("     {}", e));
+            }
+        }
+        Some(table.join("\n"))
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn supports_local(&self) -> bool {
+        false
+    }
+}
+
+struct Gather;
+
+struct Inventory {
+    json: bool,
+}
+
+impl Action for Gather {
+    fn name(&self) -> &str {
+        "facts"
+    }
+
+    fn subcommand(&self) -> App<'_, '_> {
+        SubCommand::with_name("facts")
+            .about("Collects system information and compares it across targets")
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("prints the facts as JSON"),
+            )
+    }
+
+    fn prepare(
+        &self,
+        matches: &ArgMatches,
+        _options: RunOptions,
+    ) -> Result<Arc<dyn Operation>, RemoteError> {
+        Ok(Arc::new(Inventory {
+            json: matches.is_present("json"),
+        }))
+    }
+}
+
+fn most_common<'a, F>(facts: &[&'a Facts], field: F) -> &'a str
+where
+    F: Fn(&'a Facts) -> &'a str,
+{
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for f in facts {
+        *counts.entry(field(f)).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(value, count)| (count, std::cmp::Reverse(value)))
+        .map_or("", |(value, _)| value)
+}
+
+impl Operation for Inventory {
+    fn describe(&self, _target: &Target) -> Vec<String> {
+        vec!["facts: collect system information".to_string()]
+    }
+
+    fn execute(
+        &self,
+        client: &dyn Transport,
+        _progress: &Progress,
+        _target: &Target,
+    ) -> Result<Report, RemoteError> {
+        Ok(Report::Facts(facts::gather(client)?))
+    }
+
+    fn tabulate(&self, results: &[(u8, u8, &Result<Report, RemoteError>)]) -> Option<String> {
+        if self.json {
+            let mut hosts = BTreeMap::new();
+            for (row, column, result) in results {
+                hosts.insert(
+                    crate::host_name(*row, *column),
+                    match result {
+                        Ok(Report::Facts(facts)) => serde_json::to_value(facts).unwrap(),
+                        Ok(_) => serde_json::Value::Null,
+                        Err(e) => serde_json::json!({ "error": e.to_string() }),
+                    },
+                );
+            }
+            return Some(serde_json::to_string_pretty(&hosts).unwrap());
+        }
+        let successful: Vec<&Facts> = results
+            .iter()
+            .filter_map(|(_, _, result)| match result {
+                Ok(Report::Facts(facts)) => Some(facts),
+                _ => None,
+            })
+            .collect();
+        let os = most_common(&successful, |f| f.os.as_str());
+        let kernel = most_common(&successful, |f| f.kernel.as_str());
+        let mut table = vec![format!(
+            "{:<4} {:<16} {:<24} {:<20} {:>7} {:>4} {:>8} {:>17}  {:<16} {}",
+            "HOST",
+            "HOSTNAME",
+            "OS",
+            "KERNEL",
+            "UPTIME",
+            "CPUS",
+            "MEMORY",
+            "DISK",
+            "USERS",
+            "ADDRESSES"
+        )];
+        for (row, column, result) in results {
+            table.push(match result {
+                Ok(Report::Facts(f)) => format!(
+                    "{}{:<4} {:<16} {:<24} {:<20} {:>6}h {:>4} {:>8} {:>17}  {:<16} {}{}",
+                    crossterm::SetFg(if f.os != os || f.kernel != kernel {
+                        crossterm::Color::DarkYellow
+                    } else {
+                        crossterm::Color::Green
+                    }),
+                    crate::host_name(*row, *column),
+                    f.hostname,
+                    f.os,
+                    f.kernel,
+                    f.uptime / 3600,
+                    f.cpus,
+                    crate::human_size(f.memory),
+                    format!(
+                        "{}/{}",
+                        crate::human_size(f.disk_used),
+                        crate::human_size(f.disk_total)
+                    ),
+                    match f.users.join(",").as_str() {
+                        "" => "-".to_string(),
+                        users => users.to_string(),
+                    },
+                    f.addresses.join(" "),
+                    crossterm::SetFg(crossterm::Color::Reset),
+                ),
+                Ok(_) => continue,
+                Err(e) => format!(
+                    "{}{:<4} {}{}",
+                    crossterm::SetFg(crossterm::Color::Red),
+                    crate::host_name(*row, *column),
+                    e,
+                    crossterm::SetFg(crossterm::Color::Reset),
+                ),
+            });
+        }
+        if let Some(f) = successful.first() {
+            table.push(format!("\nCPU: {}", f.cpu));
+        }
+        Some(table.join("\n"))
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,182 @@
+use clap::ArgMatches;
+use crossterm;
+use error::RemoteError;
+use history::{Entry, HostResult, Outcome};
+use std::env;
+use std::process;
+
+pub fn result(
+    row: u8,
+    column: u8,
+    result: Result<(), &RemoteError>,
+    status: Option<i32>,
+) -> HostResult {
+    HostResult {
+        host: crate::host_name(row, column),
+        outcome: match result {
+            Ok(()) => Outcome::Success,
+            Err(_) => Outcome::Failure,
+        },
+        message: result.err().map(|e| e.to_string()),
+        status: status,
+    }
+}
+
+const REDACTED: &str = "<redacted>";
+
+fn redact_value(arg: &str) -> String {
+    match arg.find('=') {
+        Some(i) => format!("{}{}", &arg[..i + 1], REDACTED),
+        None => arg.to_string(),
+    }
+}
+
+fn redact(args: Vec<String>) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut env = false;
+    for arg in args {
+        if env {
+            env = false;
+            result.push(redact_value(&arg));
+        } else if arg == "-e" || arg == "--env" {
+            env = true;
+            result.push(arg);
+        } else if let Some(value) = arg.strip_prefix("--env=") {
+            result.push(format!("--env={}", redact_value(value)));
+        } else if arg.starts_with("-e") && arg.contains('=') {
+            result.push(format!("-e{}", redact_value(&arg[2..])));
+        } else {
+            result.push(arg);
+        }
+    }
+    result
+}
+
+pub fn record(matches: &ArgMatches, results: Vec<HostResult>) {
+    let mut entry = Entry::new(
+        matches.value_of("target").unwrap(),
+        results.iter().map(|r| r.host.clone()).collect(),
+        matches.subcommand_name().unwrap_or_default(),
+        redact(env::args().skip(1).collect()),
+    );
+    entry.results = results;
+    if let Err(e) = history::append(&mut entry) {
+        println!(
+            "{}cannot write {}: {}{}",
+            crossterm::SetFg(crossterm::Color::Yellow),
+            history::path().display(),
+            e,
+            crossterm::SetFg(crossterm::Color::Reset),
+        );
+    }
+}
+
+fn color(outcome: Outcome) -> crossterm::Color {
+    match outcome {
+        Outcome::Success => crossterm::Color::Green,
+        Outcome::Failure => crossterm::Color::Red,
+        Outcome::Skipped => crossterm::Color::DarkYellow,
+    }
+}
+
+pub fn list() -> Result<(), RemoteError> {
+    for entry in history::load()? {
+        let succeeded = entry.hosts_with(Outcome::Success).len();
+        println!(
+            "{}{:>4}{} {} {} {} {} {}({}/{} succeeded){}",
+            crossterm::SetFg(crossterm::Color::Cyan),
+            entry.id,
+            crossterm::SetFg(crossterm::Color::Reset),
+            entry.timestamp,
+            entry.user,
+            entry.subcommand,
+            entry.command,
+            crossterm::SetFg(if succeeded == entry.results.len() {
+                crossterm::Color::Green
+            } else {
+                crossterm::Color::Red
+            }),
+            succeeded,
+            entry.results.len(),
+            crossterm::SetFg(crossterm::Color::Reset),
+        );
+    }
+    Ok(())
+}
+
+pub fn show(id: u64) -> Result<(), RemoteError> {
+    let entry = history::find(id)?;
+    println!("id:      {}", entry.id);
+    println!("time:    {}", entry.timestamp);
+    println!("user:    {}", entry.user);
+    println!("target:  {} ({})", entry.target, entry.hosts.join(" "));
+    println!("command: {} {}", entry.subcommand, entry.command);
+    for result in &entry.results {
+        println!(
+            "{}{} {}{}{}",
+            crossterm::SetFg(color(result.outcome)),
+            result.host,
+            match result.outcome {
+                Outcome::Success => "terminated successfully",
+                Outcome::Failure => "terminated with errors",
+                Outcome::Skipped => "skipped",
+            },
+            crossterm::SetFg(crossterm::Color::Reset),
+            match &result.message {
+                Some(message) => format!(": {}", message),
+                None => match result.status {
+                    Some(status) if status != 0 => format!(": exit status {}", status),
+                    _ => String::new(),
+                },
+            },
+        );
+    }
+    Ok(())
+}
+
+fn without_target(args: &[String], subcommand: &str) -> Vec<String> {
+    let split = args
+        .iter()
+        .position(|a| a == subcommand)
+        .unwrap_or(args.len());
+    let mut result = Vec::new();
+    let mut skip = false;
+    for arg in &args[..split] {
+        if skip {
+            skip = false;
+        } else if arg == "-t" || arg == "--target" {
+            skip = true;
+        } else if !(arg.starts_with("--target=") || arg.starts_with("-t") && arg.len() > 2) {
+            result.push(arg.clone());
+        }
+    }
+    result.extend(args[split..].iter().cloned());
+    result
+}
+
+pub fn rerun(id: u64, failed: bool) -> Result<i32, RemoteError> {
+    let entry = history::find(id)?;
+    let hosts = if failed {
+        entry.hosts_with(Outcome::Failure)
+    } else {
+        entry.hosts.clone()
+    };
+    if hosts.is_empty() {
+        return Err(RemoteError::new(&format!(
+            "no hosts to rerun for run {}",
+            id
+        )));
+    }
+    if entry.args.iter().any(|a| a.ends_with(REDACTED)) {
+        return Err(RemoteError::new(&format!(
+            "run {} set environment variables whose values were not recorded",
+            id
+        )));
+    }
+    let mut args = vec!["--target".to_string(), hosts.join(",")];
+    args.extend(without_target(&entry.args, &entry.subcommand));
+    let status = process::Command::new(env::current_exe()?)
+        .args(&args)
+        .status()?;
+    Ok(status.code().unwrap_or(1))
+}
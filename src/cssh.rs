@@ -0,0 +1,230 @@
+use core::{ConnectOptions, ShellInput, TransportKind};
+use crossterm;
+use error::RemoteError;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+const PREFIX: u8 = 0x01;
+const SCREEN_SIZE: usize = 64 * 1024;
+
+enum Event {
+    Input(Vec<u8>),
+    Output(usize, Vec<u8>),
+    Closed(usize, Result<i32, RemoteError>),
+}
+
+struct Host {
+    row: u8,
+    column: u8,
+    input: Option<Sender<ShellInput>>,
+    screen: Vec<u8>,
+    excluded: bool,
+}
+
+impl Host {
+    fn name(&self) -> String {
+        crate::host_name(self.row, self.column)
+    }
+}
+
+fn connect(
+    transport: TransportKind,
+    index: usize,
+    row: u8,
+    column: u8,
+    size: (u16, u16),
+    options: ConnectOptions,
+    events: Sender<Event>,
+) -> Sender<ShellInput> {
+    let (input, input_recv) = mpsc::channel();
+    thread::spawn(move || {
+        let (output_send, output) = mpsc::channel();
+        let output_events = events.clone();
+        let forward = thread::spawn(move || {
+            for data in output {
+                if output_events.send(Event::Output(index, data)).is_err() {
+                    break;
+                }
+            }
+        });
+        let result = transport
+            .connect(row, column, &options, &())
+            .and_then(|client| client.shell(size, input_recv, output_send));
+        forward.join().unwrap();
+        let _ = events.send(Event::Closed(index, result));
+    });
+    input
+}
+
+fn header(stdout: &mut io::Stdout, host: &Host) -> Result<(), RemoteError> {
+    write!(
+        stdout,
+        "\r\n{}== {}{} =={}\r\n",
+        crossterm::SetFg(match host.input {
+            Some(_) if host.excluded => crossterm::Color::DarkYellow,
+            Some(_) => crossterm::Color::Green,
+            None => crossterm::Color::Red,
+        }),
+        host.name(),
+        match host.input {
+            Some(_) if host.excluded => " (excluded)",
+            Some(_) => "",
+            None => " (closed)",
+        },
+        crossterm::SetFg(crossterm::Color::Reset),
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn switch(stdout: &mut io::Stdout, host: &Host) -> Result<(), RemoteError> {
+    write!(stdout, "\x1b[2J\x1b[H")?;
+    stdout.write_all(&host.screen)?;
+    header(stdout, host)
+}
+
+pub fn cssh(
+    transport: &TransportKind,
+    targets: Vec<(u8, u8)>,
+    options: &HashMap<(u8, u8), ConnectOptions>,
+) -> Result<(), RemoteError> {
+    let mut size = crate::terminal_size();
+    let (events, events_recv) = mpsc::channel();
+    let mut hosts: Vec<Host> = targets
+        .into_iter()
+        .enumerate()
+        .map(|(i, (row, column))| Host {
+            row: row,
+            column: column,
+            input: Some(connect(
+                transport.clone(),
+                i,
+                row,
+                column,
+                size,
+                options[&(row, column)].clone(),
+                events.clone(),
+            )),
+            screen: Vec::new(),
+            excluded: false,
+        })
+        .collect();
+    if hosts.is_empty() {
+        return Ok(());
+    }
+
+    let stdin_events = events.clone();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buffer = [0u8; 1024];
+        while let Ok(n) = stdin.read(&mut buffer) {
+            if n == 0
+                || stdin_events
+                    .send(Event::Input(buffer[..n].to_vec()))
+                    .is_err()
+            {
+                break;
+            }
+        }
+    });
+    drop(events);
+
+    let raw = crossterm::RawScreen::into_raw_mode()
+        .map_err(|e| RemoteError::new(&format!("cannot enter raw mode: {}", e)))?;
+    let mut stdout = io::stdout();
+    write!(
+        stdout,
+        "\x1b[2J\x1b[H^A n: next host, ^A p: previous host, ^A x: exclude host, ^A a: send ^A, ^A q: quit\r\n"
+    )?;
+    let mut current = 0;
+    header(&mut stdout, &hosts[current])?;
+
+    let mut prefix = false;
+    loop {
+        match events_recv.recv_timeout(Duration::from_millis(100)) {
+            Ok(Event::Input(data)) => {
+                let mut broadcast = Vec::new();
+                for byte in data {
+                    if !prefix {
+                        if byte == PREFIX {
+                            prefix = true;
+                        } else {
+                            broadcast.push(byte);
+                        }
+                        continue;
+                    }
+                    prefix = false;
+                    match byte {
+                        b'n' => {
+                            current = (current + 1) % hosts.len();
+                            switch(&mut stdout, &hosts[current])?;
+                        }
+                        b'p' => {
+                            current = (current + hosts.len() - 1) % hosts.len();
+                            switch(&mut stdout, &hosts[current])?;
+                        }
+                        b'x' => {
+                            hosts[current].excluded = !hosts[current].excluded;
+                            header(&mut stdout, &hosts[current])?;
+                        }
+                        b'a' | PREFIX => broadcast.push(PREFIX),
+                        b'q' => {
+                            drop(raw);
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+                if !broadcast.is_empty() {
+                    for host in hosts.iter().filter(|h| !h.excluded) {
+                        if let Some(input) = &host.input {
+                            let _ = input.send(ShellInput::Data(broadcast.clone()));
+                        }
+                    }
+                }
+            }
+            Ok(Event::Output(i, data)) => {
+                let host = &mut hosts[i];
+                host.screen.extend(&data);
+                if host.screen.len() > SCREEN_SIZE {
+                    let overflow = host.screen.len() - SCREEN_SIZE;
+                    host.screen.drain(..overflow);
+                }
+                if i == current {
+                    stdout.write_all(&data)?;
+                    stdout.flush()?;
+                }
+            }
+            Ok(Event::Closed(i, result)) => {
+                let host = &mut hosts[i];
+                host.input = None;
+                let message = match result {
+                    Ok(status) => format!("\r\n[exited with status {}]\r\n", status),
+                    Err(e) => format!("\r\n[terminated with errors: {}]\r\n", e),
+                };
+                host.screen.extend(message.as_bytes());
+                if i == current {
+                    stdout.write_all(message.as_bytes())?;
+                    header(&mut stdout, &hosts[i])?;
+                }
+                if hosts.iter().all(|h| h.input.is_none()) {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let resized = crate::terminal_size();
+        if resized != size {
+            size = resized;
+            for input in hosts.iter().filter_map(|h| h.input.as_ref()) {
+                let _ = input.send(ShellInput::Resize(size.0, size.1));
+            }
+        }
+    }
+    drop(raw);
+    Ok(())
+}
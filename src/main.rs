@@ -1,29 +1,79 @@
-use clap::{App, Arg, SubCommand};
-use core::OlinfoClient;
+use action::{Operation, Report, Target};
+use clap::{value_t, App, Arg, SubCommand};
+use config::{Config, Verdict};
+#[cfg(feature = "async")]
+use core::engine::{Engine, Task};
+use core::{
+    host, join_host_port, Become, BecomeMethod, ConnectOptions, Proxy, RunOptions, SessionPool,
+    ShellInput, SshConfig, Timeouts, TransportKind, AUTH_METHOD, USERNAME,
+};
 use crossterm;
+use error::RemoteError;
+use history::{HostResult, Outcome};
 use rand::Rng;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::thread;
-use std::time::Duration;
-use worker::{Worker, WorkerStatus};
+use std::time::{Duration, Instant};
+use worker::{Event, Progress, State, Worker, WorkerStatus};
+
+mod action;
+mod audit;
+mod cssh;
 
 const ROWS: u8 = 5;
 const COLUMNS: u8 = 4;
 
+const SPINNER: [&str; 10] = [
+    " ●    ", "  ●   ", "   ●  ", "    ● ", "     ●", "    ● ", "   ●  ", "  ●   ", " ●    ",
+    "●     ",
+];
+
+type Job = Box<dyn FnOnce(&Progress) -> Result<Report, RemoteError> + Send>;
+
 fn parse_target(arg: &str) -> Option<(u8, u8)> {
     let chars: Vec<char> = arg.chars().collect();
-    if chars.len() != 2 || !('1' <= chars[1] && chars[1] <= ('1' as u8 + COLUMNS) as char) {
+    if chars.len() != 2 || !('1' <= chars[1] && chars[1] <= (b'1' + COLUMNS) as char) {
         None
-    } else if 'a' <= chars[0] && chars[0] <= ('a' as u8 + ROWS) as char {
+    } else if 'a' <= chars[0] && chars[0] <= (b'a' + ROWS) as char {
         Some((chars[0] as u8 - 96, chars[1] as u8 - 48))
-    } else if 'A' <= chars[0] && chars[0] <= ('A' as u8 + ROWS) as char {
+    } else if 'A' <= chars[0] && chars[0] <= (b'A' + ROWS) as char {
         Some((chars[0] as u8 - 64, chars[1] as u8 - 48))
     } else {
         None
     }
 }
 
+fn host_name(row: u8, column: u8) -> String {
+    format!("{}{}", (64 + row) as char, column)
+}
+
 fn parse_targets(arg: &str) -> Option<Vec<(u8, u8)>> {
-    if arg == "all" {
+    if arg.contains(',') {
+        let mut targets = Vec::new();
+        for part in arg.split(',') {
+            for target in parse_targets(part)? {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        Some(targets)
+    } else if arg.starts_with('@') {
+        let entry = history::last().ok()??;
+        let hosts = match arg {
+            "@failed" => entry.hosts_with(Outcome::Failure),
+            "@succeeded" => entry.hosts_with(Outcome::Success),
+            "@nonzero" => entry.hosts_nonzero(),
+            _ => return None,
+        };
+        hosts.iter().map(|host| parse_target(host)).collect()
+    } else if arg == "all" {
         let mut targets = Vec::new();
         for r in 1..=ROWS {
             for c in 1..=COLUMNS {
@@ -37,259 +87,1053 @@ fn parse_targets(arg: &str) -> Option<Vec<(u8, u8)>> {
             rng.gen_range(1, ROWS + 1),
             rng.gen_range(1, COLUMNS + 1),
         )])
-    } else if let Some(t) = parse_target(arg) {
-        Some(vec![t])
     } else {
-        None
+        parse_target(arg).map(|t| vec![t])
+    }
+}
+
+fn terminal_size() -> (u16, u16) {
+    crossterm::terminal().size().unwrap_or((80, 24))
+}
+
+fn connect_options(
+    config: &Config,
+    ssh: &SshConfig,
+    jump: Option<&str>,
+    timeout: Option<u64>,
+    row: u8,
+    column: u8,
+) -> ConnectOptions {
+    let settings = ssh.lookup(&[&host_name(row, column).to_lowercase(), &host(row, column)]);
+    let proxy = match jump {
+        Some(jump) => Some(Proxy::Jump(jump.to_string())),
+        None => config.proxy(&host_name(row, column)).unwrap_or_else(|e| {
+            println!(
+                "{}invalid proxy for {}: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                host_name(row, column),
+                crossterm::SetFg(crossterm::Color::Reset),
+                e
+            );
+            process::exit(1);
+        }),
+    };
+    let mut timeouts = Timeouts::default();
+    if let Some(seconds) = timeout.or(settings.connect_timeout) {
+        timeouts.connect = Duration::from_secs(seconds);
+    }
+    ConnectOptions {
+        timeouts: timeouts,
+        proxy: proxy.or(settings.proxy),
+        host: settings.hostname,
+        port: settings.port,
+        user: settings.user,
+        identity_file: settings.identity_file,
+        known_hosts: settings.known_hosts,
+    }
+}
+
+fn shell(
+    transport: &TransportKind,
+    row: u8,
+    column: u8,
+    options: ConnectOptions,
+) -> Result<i32, RemoteError> {
+    let transport = transport.clone();
+    let mut size = terminal_size();
+    let (input, input_recv) = mpsc::channel();
+    let (output_send, output) = mpsc::channel();
+    let raw = crossterm::RawScreen::into_raw_mode()
+        .map_err(|e| RemoteError::new(&format!("cannot enter raw mode: {}", e)))?;
+    let stdin_input = input.clone();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buffer = [0u8; 1024];
+        while let Ok(n) = stdin.read(&mut buffer) {
+            if n == 0
+                || stdin_input
+                    .send(ShellInput::Data(buffer[..n].to_vec()))
+                    .is_err()
+            {
+                break;
+            }
+        }
+    });
+    let session = thread::spawn(move || {
+        transport
+            .connect(row, column, &options, &())?
+            .shell(size, input_recv, output_send)
+    });
+    let mut stdout = io::stdout();
+    loop {
+        match output.recv_timeout(Duration::from_millis(100)) {
+            Ok(data) => {
+                stdout.write_all(&data)?;
+                stdout.flush()?;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let current = terminal_size();
+        if current != size {
+            size = current;
+            let _ = input.send(ShellInput::Resize(size.0, size.1));
+        }
+    }
+    drop(raw);
+    session.join().unwrap()
+}
+
+fn read_password(prompt: &str) -> Result<String, RemoteError> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let raw = crossterm::RawScreen::into_raw_mode()
+        .map_err(|e| RemoteError::new(&format!("cannot enter raw mode: {}", e)))?;
+    let mut password = Vec::new();
+    for byte in io::stdin().lock().bytes() {
+        match byte? {
+            b'\r' | b'\n' => break,
+            3 => return Err(RemoteError::new("interrupted")),
+            8 | 127 => {
+                password.pop();
+            }
+            b => password.push(b),
+        }
+    }
+    drop(raw);
+    println!();
+    Ok(String::from_utf8(password)?)
+}
+
+fn draw(term: &crossterm::Crossterm, line: usize, row: u8, column: u8, state: &State, tick: usize) {
+    term.cursor().goto(0, line as u16).unwrap();
+    let text = match state.status {
+        WorkerStatus::Running => {
+            let mut detail = state.detail.clone();
+            if state.bytes > 0 {
+                detail.push_str(&format!(" {}", human_size(state.bytes)));
+            }
+            format!(
+                "{}{}{} {} {:.0}s {} {}",
+                crossterm::SetFg(crossterm::Color::DarkYellow),
+                (64 + row) as char,
+                column,
+                state.phase.name(),
+                state.elapsed().as_secs_f64(),
+                detail.trim(),
+                SPINNER[tick],
+            )
+        }
+        WorkerStatus::Resolved => format!(
+            "{}{}{} terminated successfully in {:.1}s",
+            crossterm::SetFg(crossterm::Color::Green),
+            (64 + row) as char,
+            column,
+            state.elapsed().as_secs_f64(),
+        ),
+        WorkerStatus::Rejected => format!(
+            "{}{}{} terminated with errors in {:.1}s",
+            crossterm::SetFg(crossterm::Color::Red),
+            (64 + row) as char,
+            column,
+            state.elapsed().as_secs_f64(),
+        ),
+    };
+    term.terminal().write(text).unwrap();
+    term.terminal()
+        .clear(crossterm::ClearType::UntilNewLine)
+        .unwrap();
+}
+
+fn wait(
+    term: &crossterm::Crossterm,
+    handles: &[(u8, u8, Worker<Report>)],
+    states: &mut [State],
+    events: &mpsc::Receiver<(usize, Event)>,
+    start: usize,
+    live: bool,
+) {
+    let interval = Duration::from_millis(150);
+    let mut tick = 0;
+    for (i, ((r, c, _), state)) in handles.iter().zip(states.iter()).enumerate().skip(start) {
+        if live {
+            draw(term, i, *r, *c, state, tick);
+        }
+    }
+    let mut running = states[start..]
+        .iter()
+        .filter(|state| state.status == WorkerStatus::Running)
+        .count();
+    let mut next = Instant::now() + interval;
+    while running > 0 {
+        let now = Instant::now();
+        if now >= next {
+            tick = (tick + 1) % SPINNER.len();
+            for (i, ((r, c, _), state)) in handles.iter().zip(states.iter()).enumerate().skip(start)
+            {
+                if live && state.status == WorkerStatus::Running {
+                    draw(term, i, *r, *c, state, tick);
+                }
+            }
+            next = now + interval;
+            continue;
+        }
+        match events.recv_timeout(next - now) {
+            Ok((id, event)) => {
+                if let Event::Finished(..) = event {
+                    running -= 1;
+                }
+                states[id].apply(event);
+                if live {
+                    draw(term, id, handles[id].0, handles[id].1, &states[id], tick);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in &["B", "K", "M", "G"] {
+        if size < 1024.0 {
+            return format!("{:.1}{}", size, unit);
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1}T", size)
+}
+
+fn outcome(r: u8, c: u8, result: &Result<Report, RemoteError>) -> HostResult {
+    audit::result(
+        r,
+        c,
+        match result {
+            Ok(report) => report.error().map_or(Ok(()), Err),
+            Err(e) => Err(e),
+        },
+        result.as_ref().ok().and_then(|report| report.status()),
+    )
+}
+
+fn finish(
+    term: &crossterm::Crossterm,
+    operation: &dyn Operation,
+    r: u8,
+    c: u8,
+    elapsed: Duration,
+    result: Result<Report, RemoteError>,
+) -> HostResult {
+    let host = outcome(r, c, &result);
+    match result {
+        Ok(report) => {
+            let (o, e) = operation.render(report);
+            let stdout = o.trim_end().to_string();
+            let stderr = e.trim_end().to_string();
+            term.terminal()
+                .write(format!(
+                    "{}{}{} terminated successfully in {:.1}s",
+                    crossterm::SetFg(crossterm::Color::Green),
+                    (64 + r) as char,
+                    c,
+                    elapsed.as_secs_f64(),
+                ))
+                .unwrap();
+            if stdout.is_empty() {
+                term.terminal().write("\n").unwrap();
+            } else if stdout.matches("\n").count() >= 1 {
+                term.terminal()
+                    .write(format!(
+                        "\n{}{}\n",
+                        crossterm::SetFg(crossterm::Color::Reset),
+                        stdout
+                    ))
+                    .unwrap();
+            } else {
+                term.terminal()
+                    .write(format!(
+                        ": {}{}\n",
+                        crossterm::SetFg(crossterm::Color::Reset),
+                        stdout
+                    ))
+                    .unwrap();
+            }
+            if !stderr.is_empty() {
+                term.terminal()
+                    .write(format!(
+                        "{}{}\n{}",
+                        crossterm::SetFg(crossterm::Color::Yellow),
+                        stderr,
+                        crossterm::SetFg(crossterm::Color::Reset)
+                    ))
+                    .unwrap();
+            }
+        }
+        Err(e) => {
+            term.terminal()
+                .write(format!(
+                    "{}{}{} terminated with errors in {:.1}s: {}{}\n",
+                    crossterm::SetFg(crossterm::Color::Red),
+                    (64 + r) as char,
+                    c,
+                    elapsed.as_secs_f64(),
+                    crossterm::SetFg(crossterm::Color::Reset),
+                    e,
+                ))
+                .unwrap();
+        }
+    }
+    host
+}
+
+#[cfg(feature = "async")]
+fn run_async(
+    term: &crossterm::Crossterm,
+    operation: &dyn Operation,
+    tasks: Vec<(u8, u8, ConnectOptions, Task)>,
+) -> Vec<HostResult> {
+    term.terminal()
+        .write(format!(
+            "{}running on {} hosts{}",
+            crossterm::SetFg(crossterm::Color::DarkYellow),
+            tasks.len(),
+            crossterm::SetFg(crossterm::Color::Reset),
+        ))
+        .unwrap();
+    let start = Instant::now();
+    let kinds: Vec<Task> = tasks.iter().map(|(_, _, _, task)| task.clone()).collect();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let outcomes = Engine::new(threads, tasks.len()).perform(tasks);
+    let elapsed = start.elapsed();
+    term.terminal().clear(crossterm::ClearType::All).unwrap();
+    term.cursor().goto(0, 0).unwrap();
+    term.cursor().show().unwrap();
+    outcomes
+        .into_iter()
+        .zip(kinds)
+        .map(|((r, c, result), task)| {
+            let report = result.and_then(|output| match (output, task) {
+                (Some(output), _) => Ok(Report::Command(output)),
+                (None, Task::Send(file)) => Ok(Report::Transfer(fs::metadata(file)?.len())),
+                (None, Task::Recv(file)) => Ok(Report::Transfer(
+                    fs::metadata(core::recv_destination(&file, r, c))?.len(),
+                )),
+                (None, Task::Run(..)) => unreachable!(),
+            });
+            finish(term, operation, r, c, elapsed, report)
+        })
+        .collect()
+}
+
+fn confirm(hosts: usize, lines: &[String], reason: &str) -> Result<bool, RemoteError> {
+    println!(
+        "{}About to execute on {} hosts ({}):{}",
+        crossterm::SetFg(crossterm::Color::Yellow),
+        hosts,
+        reason,
+        crossterm::SetFg(crossterm::Color::Reset),
+    );
+    for line in lines {
+        println!("    {}", line);
+    }
+    print!("Continue? [y/N] ");
+    io::stdout().flush()?;
+    let mut tty = io::BufReader::new(
+        fs::File::open("/dev/tty")
+            .map_err(|_| RemoteError::new("confirmation required, use --yes"))?,
+    );
+    let mut answer = String::new();
+    tty.read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+fn parse_batch(arg: &str, total: usize) -> Option<usize> {
+    if let Some(percent) = arg.strip_suffix('%') {
+        let percent: usize = percent.parse().ok()?;
+        if percent == 0 || percent > 100 {
+            return None;
+        }
+        Some((total * percent).div_ceil(100).max(1))
+    } else {
+        match arg.parse() {
+            Ok(0) | Err(_) => None,
+            Ok(size) => Some(size),
+        }
     }
 }
 
 fn main() {
-    let matches = App::new("remote")
+    let builtins = vec![
+        SubCommand::with_name("shell")
+            .about("Opens an interactive shell remotely")
+            .arg(
+                Arg::with_name("host")
+                    .required(true)
+                    .index(1)
+                    .help("host to attach to"),
+            ),
+        SubCommand::with_name("cssh").about("Broadcasts an interactive shell to every target"),
+        SubCommand::with_name("history")
+            .about("Lists past runs")
+            .subcommand(
+                SubCommand::with_name("show")
+                    .about("Shows the outcome of a past run")
+                    .arg(
+                        Arg::with_name("id")
+                            .required(true)
+                            .index(1)
+                            .help("run to show"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("rerun")
+                    .about("Repeats a past run")
+                    .arg(
+                        Arg::with_name("id")
+                            .required(true)
+                            .index(1)
+                            .help("run to repeat"),
+                    )
+                    .arg(
+                        Arg::with_name("failed")
+                            .long("failed")
+                            .help("repeats only on the hosts that failed"),
+                    ),
+            ),
+    ];
+    let reserved: Vec<&str> = builtins.iter().map(|app| app.get_name()).collect();
+    let (config, actions) = Config::load()
+        .and_then(|config| action::registry(&config, &reserved).map(|actions| (config, actions)))
+        .unwrap_or_else(|e| {
+            println!(
+                "{}invalid configuration {}: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                Config::path().display(),
+                crossterm::SetFg(crossterm::Color::Reset),
+                e
+            );
+            process::exit(1);
+        });
+    let mut app = App::new("remote")
         .version("1.0.0")
         .arg(
             Arg::with_name("target")
                 .short("t")
                 .long("target")
                 .value_name("TARGET")
-                .help("type of target [possible values: all, rand, <TARGET>] [default: all]")
+                .help(
+                    "type of target [possible values: all, rand, @failed, @succeeded, @nonzero, \
+                     <TARGET>[,<TARGET>...]] [default: all]",
+                )
                 .takes_value(true)
                 .default_value("all")
                 .hide_default_value(true),
         )
-        .subcommand(
-            SubCommand::with_name("run")
-                .about("Runs command remotely")
-                .arg(
-                    Arg::with_name("command")
-                        .required(true)
-                        .index(1)
-                        .help("command to run")
-                        .min_values(1),
-                ),
+        .arg(
+            Arg::with_name("tty")
+                .long("tty")
+                .help("requests a pseudo-terminal for remote commands"),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("skips the confirmation of large or destructive operations"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("prints what would be executed without connecting"),
         )
-        .subcommand(
-            SubCommand::with_name("firefox")
-                .about("Open web page remotely")
-                .arg(
-                    Arg::with_name("url")
-                        .required(true)
-                        .index(1)
-                        .help("url to visit"),
-                ),
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .value_name("SIZE")
+                .takes_value(true)
+                .validator(|v| match parse_batch(&v, 1) {
+                    Some(_) => Ok(()),
+                    None => Err("expected a positive number or a percentage".to_string()),
+                })
+                .help("processes targets in waves of SIZE hosts or SIZE% of the hosts"),
         )
-        .subcommand(
-            SubCommand::with_name("send")
-                .about("Loads file remotely")
-                .arg(
-                    Arg::with_name("file")
-                        .required(true)
-                        .index(1)
-                        .help("file to send"),
-                ),
+        .arg(
+            Arg::with_name("batch-pause")
+                .long("batch-pause")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("0")
+                .help("pause between waves"),
         )
-        .subcommand(
-            SubCommand::with_name("recv")
-                .about("Downloads file remotely")
-                .arg(
-                    Arg::with_name("file")
-                        .required(true)
-                        .index(1)
-                        .help("file to receive"),
-                ),
+        .arg(
+            Arg::with_name("batch-check")
+                .long("batch-check")
+                .value_name("COMMAND")
+                .takes_value(true)
+                .help("health-check command run on every host after its wave"),
+        )
+        .arg(
+            Arg::with_name("max-fail")
+                .long("max-fail")
+                .value_name("PERCENT")
+                .takes_value(true)
+                .default_value("100")
+                .help("aborts when the failure rate of a wave exceeds PERCENT"),
         )
-        .subcommand(
-            SubCommand::with_name("like")
-                .about("Put likes on forum.olinfo.it")
-                .arg(
-                    Arg::with_name("user")
-                        .required(true)
-                        .index(1)
-                        .help("user to put likes"),
-                ),
+        .arg(
+            Arg::with_name("connect-timeout")
+                .long("connect-timeout")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("5")
+                .help("timeout for opening connections"),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("transport")
+                .long("transport")
+                .value_name("TRANSPORT")
+                .takes_value(true)
+                .possible_values(&["ssh", "local"])
+                .default_value("ssh")
+                .help("how to reach the targets, local runs commands in sandbox directories"),
+        )
+        .arg(
+            Arg::with_name("proxy-jump")
+                .short("J")
+                .long("proxy-jump")
+                .value_name("[USER@]HOST[:PORT]")
+                .takes_value(true)
+                .help("connects through a jump host, overriding the configuration"),
+        )
+        .arg(
+            Arg::with_name("keepalive")
+                .long("keepalive")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("30")
+                .help("interval between keepalive messages, 0 to disable"),
+        )
+        .arg(
+            Arg::with_name("become")
+                .short("b")
+                .long("become")
+                .help("runs commands with privilege escalation"),
+        )
+        .arg(
+            Arg::with_name("become-user")
+                .long("become-user")
+                .value_name("USER")
+                .takes_value(true)
+                .default_value("root")
+                .help("user to become"),
+        )
+        .arg(
+            Arg::with_name("become-method")
+                .long("become-method")
+                .value_name("METHOD")
+                .takes_value(true)
+                .possible_values(&["sudo", "su"])
+                .default_value("sudo")
+                .help("privilege escalation method"),
+        )
+        .arg(
+            Arg::with_name("ask-become-pass")
+                .short("K")
+                .long("ask-become-pass")
+                .requires("become")
+                .help("asks for the privilege escalation password [env: REMOTE_BECOME_PASSWORD]"),
+        );
+    for builtin in builtins {
+        app = app.subcommand(builtin);
+    }
+    for action in &actions {
+        app = app.subcommand(action.subcommand());
+    }
+    if cfg!(feature = "async") {
+        app = app.arg(
+            Arg::with_name("async")
+                .long("async")
+                .help("runs run, send and recv on the non-blocking engine"),
+        );
+    }
+    let matches = app.get_matches();
 
-    let targets: Vec<(u8, u8)> = parse_targets(matches.value_of("target").unwrap()).unwrap();
+    if let Some(matches) = matches.subcommand_matches("history") {
+        let result = if let Some(matches) = matches.subcommand_matches("show") {
+            audit::show(value_t!(matches, "id", u64).unwrap_or_else(|e| e.exit()))
+        } else if let Some(matches) = matches.subcommand_matches("rerun") {
+            audit::rerun(
+                value_t!(matches, "id", u64).unwrap_or_else(|e| e.exit()),
+                matches.is_present("failed"),
+            )
+            .map(|status| process::exit(status))
+        } else {
+            audit::list()
+        };
+        if let Err(e) = result {
+            println!(
+                "{}history: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                crossterm::SetFg(crossterm::Color::Reset),
+                e
+            );
+            process::exit(1);
+        }
+        return;
+    }
 
-    let mut handles = Vec::new();
-    if let Some(matches) = matches.subcommand_matches("run") {
-        let command: String = matches
-            .values_of("command")
-            .unwrap()
-            .collect::<Vec<&str>>()
-            .join(" ");
-        for (row, column) in targets {
-            let c = command.clone();
-            handles.push((
+    let ssh = SshConfig::load().unwrap_or_else(|e| {
+        println!(
+            "{}invalid ssh configuration {}: {}{}",
+            crossterm::SetFg(crossterm::Color::Red),
+            SshConfig::path().display(),
+            crossterm::SetFg(crossterm::Color::Reset),
+            e
+        );
+        process::exit(1);
+    });
+    let timeout = if matches.occurrences_of("connect-timeout") > 0 {
+        Some(value_t!(matches, "connect-timeout", u64).unwrap_or_else(|e| e.exit()))
+    } else {
+        None
+    };
+    let jump = matches.value_of("proxy-jump");
+    let transport = match matches.value_of("transport").unwrap() {
+        "local" => TransportKind::Local(match env::var_os("REMOTE_LOCAL_ROOT") {
+            Some(root) => PathBuf::from(root),
+            None => env::temp_dir().join("remote-local"),
+        }),
+        _ => TransportKind::Ssh,
+    };
+
+    if let Some(submatches) = matches.subcommand_matches("shell") {
+        let host = submatches.value_of("host").unwrap();
+        let (row, column) = parse_target(host).unwrap_or_else(|| {
+            println!(
+                "{}invalid target: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                crossterm::SetFg(crossterm::Color::Reset),
+                host
+            );
+            process::exit(1);
+        });
+        let result = shell(
+            &transport,
+            row,
+            column,
+            connect_options(&config, &ssh, jump, timeout, row, column),
+        );
+        audit::record(
+            &matches,
+            vec![audit::result(
                 row,
                 column,
-                Worker::new(move || OlinfoClient::new(row, column)?.run(c)),
-            ));
+                result.as_ref().map(|_| ()),
+                result.as_ref().ok().cloned(),
+            )],
+        );
+        match result {
+            Ok(status) => process::exit(status),
+            Err(e) => {
+                println!(
+                    "{}{}{} terminated with errors: {}{}",
+                    crossterm::SetFg(crossterm::Color::Red),
+                    (64 + row) as char,
+                    column,
+                    crossterm::SetFg(crossterm::Color::Reset),
+                    e,
+                );
+                process::exit(1);
+            }
         }
-    } else if let Some(matches) = matches.subcommand_matches("firefox") {
-        let command = format!(
-            "DISPLAY=\":0\" firefox \"{}\"",
-            matches.value_of("url").unwrap()
+    }
+
+    let action = actions
+        .iter()
+        .find(|action| matches.subcommand_name() == Some(action.name()));
+    let target = match action.and_then(|action| action.targets()) {
+        Some(target) if matches.occurrences_of("target") == 0 => target,
+        _ => matches.value_of("target").unwrap(),
+    };
+    let targets: Vec<(u8, u8)> = parse_targets(target).unwrap_or_else(|| {
+        println!(
+            "{}invalid target: {}{}",
+            crossterm::SetFg(crossterm::Color::Red),
+            crossterm::SetFg(crossterm::Color::Reset),
+            target
         );
-        for (row, column) in targets {
-            let c = command.clone();
-            handles.push((
-                row,
-                column,
-                Worker::new(move || OlinfoClient::new(row, column)?.run(c)),
-            ));
+        process::exit(1);
+    });
+    if targets.is_empty() {
+        println!(
+            "{}no hosts match {} in the last run{}",
+            crossterm::SetFg(crossterm::Color::Red),
+            target,
+            crossterm::SetFg(crossterm::Color::Reset),
+        );
+        process::exit(1);
+    }
+    let options: HashMap<(u8, u8), ConnectOptions> = targets
+        .iter()
+        .map(|&(row, column)| {
+            (
+                (row, column),
+                connect_options(&config, &ssh, jump, timeout, row, column),
+            )
+        })
+        .collect();
+    if matches.subcommand_matches("cssh").is_some() {
+        let result = cssh::cssh(&transport, targets.clone(), &options);
+        audit::record(
+            &matches,
+            targets
+                .iter()
+                .map(|&(row, column)| audit::result(row, column, result.as_ref().map(|_| ()), None))
+                .collect(),
+        );
+        if let Err(e) = result {
+            println!(
+                "{}cssh terminated with errors: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                crossterm::SetFg(crossterm::Color::Reset),
+                e,
+            );
+            process::exit(1);
         }
-    } else if let Some(matches) = matches.subcommand_matches("send") {
-        let file = matches.value_of("file").unwrap().to_string();
-        for (row, column) in targets {
-            let f = file.clone();
-            handles.push((
+        return;
+    }
+
+    let tty = if matches.is_present("tty") {
+        Some(terminal_size())
+    } else {
+        None
+    };
+
+    let escalation = if matches.is_present("become") {
+        let password = if matches.is_present("ask-become-pass") {
+            Some(read_password("become password: ").unwrap_or_else(|e| {
+                println!(
+                    "{}cannot read the become password: {}{}",
+                    crossterm::SetFg(crossterm::Color::Red),
+                    crossterm::SetFg(crossterm::Color::Reset),
+                    e
+                );
+                process::exit(1);
+            }))
+        } else {
+            env::var("REMOTE_BECOME_PASSWORD").ok()
+        };
+        Some(Become {
+            method: BecomeMethod::parse(matches.value_of("become-method").unwrap()).unwrap(),
+            user: matches.value_of("become-user").unwrap().to_string(),
+            password: password,
+        })
+    } else {
+        None
+    };
+
+    let pool_options = options.clone();
+    let pool_transport = transport.clone();
+    let pool = SessionPool::new(
+        value_t!(matches, "keepalive", u32).unwrap_or_else(|e| e.exit()),
+        move |row, column, observer| {
+            pool_transport.connect(
                 row,
                 column,
-                Worker::new(move || {
-                    OlinfoClient::new(row, column)?.send(f)?;
-                    Ok((String::new(), String::new()))
-                }),
-            ));
+                &pool_options
+                    .get(&(row, column))
+                    .cloned()
+                    .unwrap_or_default(),
+                observer,
+            )
+        },
+    );
+    let action = match action {
+        Some(action) => action,
+        None => return,
+    };
+    let operation = action
+        .prepare(
+            matches.subcommand_matches(action.name()).unwrap(),
+            RunOptions {
+                pty: tty,
+                escalation: escalation,
+                ..Default::default()
+            },
+        )
+        .unwrap_or_else(|e| {
+            println!(
+                "{}{}: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                action.name(),
+                crossterm::SetFg(crossterm::Color::Reset),
+                e
+            );
+            process::exit(1);
+        });
+    if let TransportKind::Local(_) = transport {
+        if !operation.supports_local() {
+            println!(
+                "{}{} is not supported by the local transport{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                action.name(),
+                crossterm::SetFg(crossterm::Color::Reset),
+            );
+            process::exit(1);
         }
-    } else if let Some(matches) = matches.subcommand_matches("recv") {
-        let file = matches.value_of("file").unwrap().to_string();
-        for (row, column) in targets {
-            let f = file.clone();
-            handles.push((
-                row,
-                column,
-                Worker::new(move || {
-                    OlinfoClient::new(row, column)?.recv(f)?;
-                    Ok((String::new(), String::new()))
-                }),
-            ));
+    }
+    let mut jobs: Vec<(u8, u8, Job)> = Vec::new();
+    let mut plans: Vec<(u8, u8, Vec<String>)> = Vec::new();
+    #[cfg(feature = "async")]
+    let mut tasks = Vec::new();
+    for (row, column) in targets {
+        let target = Target::new(
+            row,
+            column,
+            options[&(row, column)].clone(),
+            config.vars(&host_name(row, column)),
+        );
+        plans.push((row, column, operation.describe(&target)));
+        #[cfg(feature = "async")]
+        tasks.push((
+            row,
+            column,
+            options[&(row, column)].clone(),
+            operation.task(&target),
+        ));
+        let o = operation.clone();
+        let p = pool.clone();
+        jobs.push((
+            row,
+            column,
+            Box::new(move |progress: &Progress| o.perform(&p, progress, &target)),
+        ));
+    }
+
+    #[cfg(feature = "async")]
+    let tasks: Vec<(u8, u8, ConnectOptions, Task)> = if matches.is_present("async") {
+        if matches!(transport, TransportKind::Local(_))
+            || matches.is_present("batch")
+            || matches.is_present("batch-check")
+            || tasks.iter().any(|(_, _, _, task)| task.is_none())
+        {
+            println!(
+                "{}--async supports run, send and recv over ssh, without --batch or --batch-check{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                crossterm::SetFg(crossterm::Color::Reset),
+            );
+            process::exit(1);
         }
-    } else if let Some(matches) = matches.subcommand_matches("like") {
-        let user = matches.value_of("user").unwrap().to_string();
-        for (row, column) in targets {
-            let u = user.clone();
-            handles.push((
-                row,
-                column,
-                Worker::new(move || OlinfoClient::new(row, column)?.like(u)),
-            ));
+        tasks
+            .into_iter()
+            .map(|(row, column, options, task)| (row, column, options, task.unwrap()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if let Some(check) = matches.value_of("batch-check") {
+        jobs = jobs
+            .into_iter()
+            .map(|(row, column, job)| {
+                let c = check.to_string();
+                let p = pool.clone();
+                let job: Job = Box::new(move |progress: &Progress| {
+                    let result = job(progress)?;
+                    progress.detail("health check".to_string());
+                    let output = p.with(row, column, progress, |client| {
+                        client.exec(&c, &RunOptions::default())
+                    })?;
+                    if output.status != 0 {
+                        return Err(RemoteError::new(&format!(
+                            "health check failed with exit status {}",
+                            output.status
+                        )));
+                    }
+                    Ok(result)
+                });
+                (row, column, job)
+            })
+            .collect();
+    }
+
+    if matches.is_present("dry-run") {
+        for (row, column, plan) in plans {
+            let o = &options[&(row, column)];
+            if let TransportKind::Local(root) = &transport {
+                println!(
+                    "{}{} local sandbox {}{}",
+                    crossterm::SetFg(crossterm::Color::Cyan),
+                    host_name(row, column),
+                    root.join(host_name(row, column)).display(),
+                    crossterm::SetFg(crossterm::Color::Reset),
+                );
+            } else {
+                println!(
+                    "{}{}{} {} as {} ({} authentication){}{}",
+                    crossterm::SetFg(crossterm::Color::Cyan),
+                    (64 + row) as char,
+                    column,
+                    join_host_port(
+                        &o.host.clone().unwrap_or_else(|| host(row, column)),
+                        o.port.unwrap_or(22)
+                    ),
+                    o.user.as_ref().map_or(USERNAME, |u| u.as_str()),
+                    match &o.identity_file {
+                        Some(identity) => format!("{} or {}", identity.display(), AUTH_METHOD),
+                        None => AUTH_METHOD.to_string(),
+                    },
+                    match &o.proxy {
+                        Some(Proxy::Jump(jump)) => format!(" via {}", jump),
+                        Some(Proxy::Command(command)) => format!(" via `{}`", command),
+                        None => String::new(),
+                    },
+                    crossterm::SetFg(crossterm::Color::Reset),
+                );
+            }
+            for line in plan {
+                println!("    {}", line);
+            }
+            if let Some(check) = matches.value_of("batch-check") {
+                println!("    check: {}", check);
+            }
+        }
+        return;
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    for (_, _, plan) in &plans {
+        for line in plan {
+            if !lines.contains(line) {
+                lines.push(line.clone());
+            }
+        }
+    }
+    lines.extend(
+        matches
+            .value_of("batch-check")
+            .map(|c| format!("check: {}", c)),
+    );
+    let proceed = match config.confirm.check(plans.len(), &lines) {
+        Ok(Verdict::Proceed) => Ok(true),
+        Ok(Verdict::Confirm(_)) if matches.is_present("yes") || operation.read_only() => Ok(true),
+        Ok(Verdict::Confirm(reason)) => confirm(plans.len(), &lines, &reason),
+        Ok(Verdict::Deny(pattern)) => Err(RemoteError::new(&format!(
+            "denied by pattern {} in {}",
+            pattern,
+            Config::path().display()
+        ))),
+        Err(e) => Err(e),
+    };
+    match proceed {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            println!(
+                "{}aborted: {}{}",
+                crossterm::SetFg(crossterm::Color::Red),
+                crossterm::SetFg(crossterm::Color::Reset),
+                e
+            );
+            process::exit(1);
         }
     }
 
     let term = crossterm::Crossterm::new();
+    let live = io::stdout().is_terminal();
 
-    term.terminal().clear(crossterm::ClearType::All).unwrap();
-    term.cursor().hide().unwrap();
+    if live {
+        term.terminal().clear(crossterm::ClearType::All).unwrap();
+        term.cursor().hide().unwrap();
+    }
 
-    let mut state = 0;
-    let spinner = vec![
-        " ●    ", "  ●   ", "   ●  ", "    ● ", "     ●", "    ● ", "   ●  ", "  ●   ", " ●    ",
-        "●     ",
-    ];
-    loop {
-        term.cursor().goto(0, 0).unwrap();
-        let mut running = 0;
-        for (r, c, w) in &handles {
-            match w.get_status() {
-                WorkerStatus::Running => {
-                    running += 1;
-                    term.terminal()
-                        .write(format!(
-                            "{}{}{} running {}\n",
-                            crossterm::SetFg(crossterm::Color::DarkYellow),
-                            (64 + *r) as char,
-                            c,
-                            spinner[state],
-                        ))
-                        .unwrap()
-                }
-                WorkerStatus::Resolved => term
-                    .terminal()
-                    .write(format!(
-                        "{}{}{} terminated successfully\n",
-                        crossterm::SetFg(crossterm::Color::Green),
-                        (64 + *r) as char,
-                        c,
-                    ))
-                    .unwrap(),
-                WorkerStatus::Rejected => term
-                    .terminal()
-                    .write(format!(
-                        "{}{}{} terminated with errors\n",
-                        crossterm::SetFg(crossterm::Color::Red),
-                        (64 + *r) as char,
-                        c,
-                    ))
-                    .unwrap(),
-            };
+    #[cfg(feature = "async")]
+    {
+        if !tasks.is_empty() {
+            let results = run_async(&term, operation.as_ref(), tasks);
+            audit::record(&matches, results);
+            return;
         }
-        if running == 0 {
+    }
+
+    let batch = match matches.value_of("batch") {
+        Some(batch) => parse_batch(batch, jobs.len()).unwrap(),
+        None => jobs.len().max(1),
+    };
+    let max_fail = value_t!(matches, "max-fail", usize).unwrap_or_else(|e| e.exit());
+    let pause = value_t!(matches, "batch-pause", u64).unwrap_or_else(|e| e.exit());
+    let (events, receiver) = mpsc::channel();
+    let mut handles = Vec::new();
+    let mut states = Vec::new();
+    let mut skipped = Vec::new();
+    let mut jobs = jobs.into_iter().peekable();
+    while jobs.peek().is_some() {
+        let start = handles.len();
+        for (row, column, job) in jobs.by_ref().take(batch) {
+            let worker = Worker::new(handles.len(), &events, job);
+            states.push(State::new(worker.started()));
+            handles.push((row, column, worker));
+        }
+        wait(&term, &handles, &mut states, &receiver, start, live);
+        let failures = states[start..]
+            .iter()
+            .filter(|state| state.status == WorkerStatus::Rejected)
+            .count();
+        if failures * 100 > max_fail * (handles.len() - start) {
+            skipped.extend(jobs.by_ref().map(|(row, column, _)| (row, column)));
             break;
         }
-        thread::sleep(Duration::from_millis(150));
-        state = (state + 1) % spinner.len();
+        if pause > 0 && jobs.peek().is_some() {
+            thread::sleep(Duration::from_secs(pause));
+        }
     }
 
-    term.terminal().clear(crossterm::ClearType::All).unwrap();
-    term.cursor().goto(0, 0).unwrap();
-    term.cursor().show().unwrap();
-    for (r, c, t) in handles {
-        match t.join() {
-            Ok((o, e)) => {
-                let stdout = o.trim_end().to_string();
-                let stderr = e.trim_end().to_string();
-                term.terminal()
-                    .write(format!(
-                        "{}{}{} terminated successfully",
-                        crossterm::SetFg(crossterm::Color::Green),
-                        (64 + r) as char,
-                        c
-                    ))
-                    .unwrap();
-                if stdout.is_empty() {
-                    term.terminal().write("\n").unwrap();
-                } else if stdout.matches("\n").count() >= 1 {
-                    term.terminal()
-                        .write(format!(
-                            "\n{}{}\n",
-                            crossterm::SetFg(crossterm::Color::Reset),
-                            stdout
-                        ))
-                        .unwrap();
-                } else {
-                    term.terminal()
-                        .write(format!(
-                            ": {}{}\n",
-                            crossterm::SetFg(crossterm::Color::Reset),
-                            stdout
-                        ))
-                        .unwrap();
-                }
-                if stderr.len() > 0 {
-                    term.terminal()
-                        .write(format!(
-                            "{}{}\n{}",
-                            crossterm::SetFg(crossterm::Color::Yellow),
-                            stderr,
-                            crossterm::SetFg(crossterm::Color::Reset)
-                        ))
-                        .unwrap();
-                }
+    if live {
+        term.terminal().clear(crossterm::ClearType::All).unwrap();
+        term.cursor().goto(0, 0).unwrap();
+        term.cursor().show().unwrap();
+    }
+    let finished: Vec<(u8, u8, Duration, Result<Report, RemoteError>)> = handles
+        .into_iter()
+        .zip(states)
+        .map(|((r, c, t), state)| (r, c, state.elapsed(), t.join()))
+        .collect();
+    let table = operation.tabulate(
+        &finished
+            .iter()
+            .map(|(r, c, _, result)| (*r, *c, result))
+            .collect::<Vec<_>>(),
+    );
+    let mut results = Vec::new();
+    match table {
+        Some(table) => {
+            term.terminal().write(format!("{}\n", table)).unwrap();
+            for (r, c, _, result) in &finished {
+                results.push(outcome(*r, *c, result));
             }
-            Err(e) => {
-                term.terminal()
-                    .write(format!(
-                        "{}{}{} terminated with errors: {}{}\n",
-                        crossterm::SetFg(crossterm::Color::Red),
-                        (64 + r) as char,
-                        c,
-                        crossterm::SetFg(crossterm::Color::Reset),
-                        e,
-                    ))
-                    .unwrap();
+        }
+        None => {
+            for (r, c, elapsed, result) in finished {
+                results.push(finish(&term, operation.as_ref(), r, c, elapsed, result));
             }
         }
     }
+    for (r, c) in skipped {
+        results.push(HostResult {
+            host: host_name(r, c),
+            outcome: Outcome::Skipped,
+            message: None,
+            status: None,
+        });
+        term.terminal()
+            .write(format!(
+                "{}{}{} skipped{}\n",
+                crossterm::SetFg(crossterm::Color::DarkYellow),
+                (64 + r) as char,
+                c,
+                crossterm::SetFg(crossterm::Color::Reset),
+            ))
+            .unwrap();
+    }
+    audit::record(&matches, results);
 }
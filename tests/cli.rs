@@ -0,0 +1,327 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+use testing::Sshd;
+
+fn home() -> TempDir {
+    let home = tempfile::Builder::new()
+        .prefix("remote-home")
+        .tempdir()
+        .unwrap();
+    fs::create_dir_all(home.path().join(".ssh")).unwrap();
+    home
+}
+
+fn remote(home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_remote"))
+        .args(args)
+        .current_dir(home)
+        .env("HOME", home)
+        .env_remove("XDG_DATA_HOME")
+        .env("REMOTE_CONFIG", home.join("config.toml"))
+        .env("REMOTE_LOCAL_ROOT", home.join("seats"))
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn local_run_reaches_every_seat() {
+    let home = home();
+    let output = remote(
+        home.path(),
+        &[
+            "--transport",
+            "local",
+            "-t",
+            "a1,b2",
+            "run",
+            "echo seat > seat",
+        ],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    for seat in &["A1", "B2"] {
+        let path = home.path().join("seats").join(seat).join("seat");
+        assert_eq!(fs::read_to_string(path).unwrap(), "seat\n");
+    }
+    let history = fs::read_to_string(home.path().join(".local/share/remote/history.jsonl"));
+    assert!(history.unwrap().contains("\"subcommand\":\"run\""));
+}
+
+#[test]
+fn history_redacts_env_values() {
+    let home = home();
+    let output = remote(
+        home.path(),
+        &[
+            "--transport",
+            "local",
+            "-t",
+            "a1",
+            "run",
+            "-e",
+            "SECRET=hunter2",
+            "echo $SECRET",
+        ],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "a1", "run", "true"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    let history =
+        fs::read_to_string(home.path().join(".local/share/remote/history.jsonl")).unwrap();
+    assert!(!history.contains("hunter2"));
+    assert!(history.contains("\"id\":2"));
+    let output = remote(home.path(), &["history", "rerun", "1"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn empty_history_selection_fails() {
+    let home = home();
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "a1", "run", "true"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "@failed", "run", "true"],
+    );
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("no hosts match @failed in the last run"));
+}
+
+#[test]
+fn shell_rejects_invalid_host() {
+    let home = home();
+    let output = remote(home.path(), &["shell", "zz"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout(&output).contains("invalid target"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn local_send_and_recv() {
+    let home = home();
+    fs::write(home.path().join("notes.txt"), "notes").unwrap();
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "c3", "send", "notes.txt"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        fs::read_to_string(home.path().join("seats/C3/notes.txt")).unwrap(),
+        "notes"
+    );
+
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "c3", "recv", "notes.txt"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        fs::read_to_string(home.path().join("notes_C3.txt")).unwrap(),
+        "notes"
+    );
+}
+
+#[test]
+fn dry_run_does_not_execute() {
+    let home = home();
+    let output = remote(
+        home.path(),
+        &[
+            "--transport",
+            "local",
+            "--dry-run",
+            "-t",
+            "a1",
+            "run",
+            "touch marker",
+        ],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("run: touch marker"));
+    assert!(!home.path().join("seats/A1/marker").exists());
+}
+
+#[test]
+fn denied_commands_are_refused() {
+    let home = home();
+    fs::write(
+        home.path().join("config.toml"),
+        "[confirm]\ndeny = [\"forbidden\"]\n",
+    )
+    .unwrap();
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "a1", "run", "echo forbidden"],
+    );
+    assert!(!output.status.success());
+
+    let output = remote(
+        home.path(),
+        &[
+            "--transport",
+            "local",
+            "--yes",
+            "-t",
+            "a1",
+            "run",
+            "touch forbidden",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(!home.path().join("seats/A1/forbidden").exists());
+}
+
+#[test]
+fn aliases_expand_parameters() {
+    let home = home();
+    fs::write(
+        home.path().join("config.toml"),
+        "[aliases.mark]\n\
+         command = \"echo {text} > {file}\"\n\
+         args = [\"text\"]\n\
+         options = { file = \"marker\" }\n\
+         targets = \"b1\"\n",
+    )
+    .unwrap();
+    let output = remote(home.path(), &["--help"]);
+    assert!(stdout(&output).contains("mark"), "{}", stdout(&output));
+
+    let output = remote(home.path(), &["--transport", "local", "mark", "it's $HOME"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        fs::read_to_string(home.path().join("seats/B1/marker")).unwrap(),
+        "it's $HOME\n"
+    );
+    assert!(!home.path().join("seats/A1").exists());
+
+    let output = remote(
+        home.path(),
+        &[
+            "--transport",
+            "local",
+            "mark",
+            "{host} '{col}'",
+            "--file",
+            "{host}",
+        ],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        fs::read_to_string(home.path().join("seats/B1/{host}")).unwrap(),
+        "{host} '{col}'\n"
+    );
+}
+
+#[test]
+fn local_facts_report_sizes() {
+    let home = home();
+    let output = remote(
+        home.path(),
+        &["--transport", "local", "-t", "a1", "facts", "--json"],
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    let text = stdout(&output);
+    for key in &["memory", "disk_total"] {
+        let value: u64 = text
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(&format!("\"{}\": ", key)))
+            .unwrap()
+            .trim_end_matches(',')
+            .parse()
+            .unwrap();
+        assert!(value > 0 && value.is_multiple_of(1024), "{}", text);
+    }
+}
+
+#[test]
+fn duplicate_alias_parameters_are_rejected() {
+    let home = home();
+    fs::write(
+        home.path().join("config.toml"),
+        "[aliases.mark]\n\
+         command = \"echo {x}\"\n\
+         args = [\"x\"]\n\
+         options = { x = \"1\" }\n",
+    )
+    .unwrap();
+    let output = remote(home.path(), &["--help"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout(&output).contains("duplicate or reserved parameter x"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn placeholders_expand_per_seat() {
+    let home = home();
+    fs::write(
+        home.path().join("config.toml"),
+        "[vars]\ncontest = \"ioi\"\n\n\
+         [groups.finals]\ntargets = [\"B2\"]\nvars = { contest = \"ioi finals\" }\n",
+    )
+    .unwrap();
+    let args = [
+        "--transport",
+        "local",
+        "-t",
+        "a1,b2",
+        "run",
+        "echo {host} {row} {col} {contest} {nope} '{{.Names}}' > seat",
+    ];
+    let output = remote(home.path(), &[&["--dry-run"][..], &args[..]].concat());
+    let text = stdout(&output);
+    assert!(
+        text.contains("run: echo B2 B 2 'ioi finals' {nope} '{{.Names}}' > seat"),
+        "{}",
+        text
+    );
+    assert!(!home.path().join("seats/B2/seat").exists());
+
+    let output = remote(home.path(), &args);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(
+        fs::read_to_string(home.path().join("seats/A1/seat")).unwrap(),
+        "A1 A 1 ioi {nope} {{.Names}}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(home.path().join("seats/B2/seat")).unwrap(),
+        "B2 B 2 ioi finals {nope} {{.Names}}\n"
+    );
+}
+
+#[test]
+#[ignore = "needs sshd"]
+fn ssh_run_against_fake_seats() {
+    let seats = [("a1", Sshd::start()), ("a2", Sshd::start())];
+    let home = home();
+    let config: String = seats
+        .iter()
+        .map(|(seat, sshd)| sshd.ssh_config(seat))
+        .collect();
+    fs::write(home.path().join(".ssh/config"), config).unwrap();
+    let output = remote(home.path(), &["-t", "a1,a2", "run", "echo", "hello"]);
+    let text = stdout(&output);
+    assert!(output.status.success(), "{}", text);
+    assert!(text.contains("A1 terminated successfully"), "{}", text);
+    assert!(text.contains("A2 terminated successfully"), "{}", text);
+    assert!(text.contains("hello"), "{}", text);
+
+    let output = remote(home.path(), &["-t", "a1,a2", "ping"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+}